@@ -1,4 +1,5 @@
 use bazaar::inventory::{describe_change, detect_changes, Entry};
+use bazaar::inventory_binary::{InventoryBinaryError, InventoryBinaryWriteError, InventoryReader};
 use bazaar::inventory_delta::{
     InventoryDeltaEntry, InventoryDeltaInconsistency, InventoryDeltaParseError,
     InventoryDeltaSerializeError,
@@ -22,6 +23,7 @@ import_exception!(breezy.errors, InconsistentDelta);
 import_exception!(breezy.errors, BzrError);
 create_exception!(breezy.inventory_delta, IncompatibleInventoryDelta, BzrError);
 create_exception!(breezy.inventory_delta, InventoryDeltaError, BzrError);
+create_exception!(breezy.inventory_delta, InventoryBinaryFormatError, BzrError);
 
 fn kind_from_str(kind: &str) -> Option<Kind> {
     match kind {
@@ -213,20 +215,30 @@ impl InventoryEntry {
     /// Do deal with a short lived bug in bzr 0.8's development two entries
     /// that have the same last changed but different 'x' bit settings are
     /// changed in-place.
+    ///
+    /// `get_parent_map` is an optional callable taking a single revision id
+    /// (bytes) and returning the list of its parent revision ids for this
+    /// file's graph (e.g. `Repository.get_file_graph(file_id).get_parent_map`).
+    /// When given, the last-changed revisions gathered above are reduced to
+    /// only the heads of that graph, rather than returning every one of
+    /// them.
+    #[pyo3(signature = (previous_inventories, get_parent_map=None))]
     fn parent_candidates(
         &self,
         py: Python,
         previous_inventories: Vec<PyObject>,
+        get_parent_map: Option<PyObject>,
     ) -> PyResult<PyObject> {
         // revision:ie mapping for each ie found in previous_inventories
-        let mut candidates: HashMap<&RevisionId, PyObject> = HashMap::new();
+        let mut candidates: HashMap<RevisionId, PyObject> = HashMap::new();
+        let mut order: Vec<RevisionId> = Vec::new();
         // identify candidate head revision ids
         for inv in previous_inventories {
             match inv.call_method1(py, "get_entry", (self.get_file_id(py),)) {
                 Ok(py_entry) => {
                     if let Ok(mut entry) = py_entry.extract::<PyRefMut<InventoryEntry>>(py) {
-                        if let Some(revision) = entry.0.revision() {
-                            if let Some(candidate) = candidates.get_mut(revision) {
+                        if let Some(revision) = entry.0.revision().cloned() {
+                            if let Some(candidate) = candidates.get(&revision).cloned() {
                                 // same revision value in two different inventories:
                                 // correct possible inconsistencies:
                                 //  * there was a bug in revision updates with executable bit support
@@ -252,7 +264,8 @@ impl InventoryEntry {
                                 }
                             } else {
                                 // add this revision as a candidate.
-                                //candidates.insert(revision, py_entry);
+                                order.push(revision.clone());
+                                candidates.insert(revision, py_entry);
                             }
                         }
                     }
@@ -263,9 +276,22 @@ impl InventoryEntry {
                 }
             }
         }
+
+        if let Some(get_parent_map) = get_parent_map {
+            let heads = bazaar::inventory::heads(&order, |revision| -> Vec<RevisionId> {
+                get_parent_map
+                    .call1(py, (PyBytes::new(py, revision.bytes()),))
+                    .ok()
+                    .and_then(|parents| parents.extract::<Vec<Vec<u8>>>(py).ok())
+                    .map(|parents| parents.into_iter().map(RevisionId::from).collect())
+                    .unwrap_or_default()
+            });
+            candidates.retain(|revision, _| heads.contains(revision));
+        }
+
         let ret = PyDict::new(py);
         for (revision, entry) in candidates.iter() {
-            ret.set_item(PyBytes::new(py, &revision.bytes()), entry)?;
+            ret.set_item(PyBytes::new(py, revision.bytes()), entry)?;
         }
         Ok(ret.into_py(py))
     }
@@ -908,33 +934,38 @@ impl InventoryDelta {
     }
 
     fn check(&self) -> PyResult<()> {
-        self.0.check().map_err(|e| match e {
-            InventoryDeltaInconsistency::NoPath => {
-                InconsistentDelta::new_err(("", "", "No path in entry"))
-            }
-            InventoryDeltaInconsistency::DuplicateFileId(ref path, ref fid) => {
-                InconsistentDelta::new_err((path.clone(), fid.bytes().to_vec(), "repeated file_id"))
-            }
-            InventoryDeltaInconsistency::DuplicateOldPath(path, fid) => {
-                InconsistentDelta::new_err((path, fid.bytes().to_vec(), "repeated path"))
-            }
-            InventoryDeltaInconsistency::DuplicateNewPath(path, fid) => {
-                InconsistentDelta::new_err((path, fid.bytes().to_vec(), "repeated path"))
-            }
-            InventoryDeltaInconsistency::MismatchedId(path, fid1, fid2) => {
-                InconsistentDelta::new_err((
-                    path,
-                    fid1.bytes().to_vec(),
-                    format!("mismatched id with entry {}", fid2),
-                ))
-            }
-            InventoryDeltaInconsistency::EntryWithoutPath(path, fid) => {
-                InconsistentDelta::new_err((path, fid.bytes().to_vec(), "Entry with no new_path"))
-            }
-            InventoryDeltaInconsistency::PathWithoutEntry(path, fid) => {
-                InconsistentDelta::new_err((path, fid.bytes().to_vec(), "new_path with no entry"))
-            }
-        })
+        self.0.check().map_err(inconsistency_to_err)
+    }
+
+    /// Compose `self` (mapping inventory A to B) with `other` (mapping B
+    /// to C) into a single delta mapping A to C.
+    fn compose(&self, other: &InventoryDelta) -> PyResult<InventoryDelta> {
+        let composed = InventoryDelta(self.0.compose(&other.0));
+        composed.check()?;
+        Ok(composed)
+    }
+
+    /// Diff `old_entries` against `new_entries` (each the complete set of
+    /// [`InventoryEntry`] values of an inventory), producing the delta
+    /// that `apply_to(old_entries)` would turn back into `new_entries`.
+    #[staticmethod]
+    fn between(
+        old_entries: Vec<PyRef<InventoryEntry>>,
+        new_entries: Vec<PyRef<InventoryEntry>>,
+    ) -> InventoryDelta {
+        let old: bazaar::inventory::Inventory = old_entries.iter().map(|e| e.0.clone()).collect();
+        let new: bazaar::inventory::Inventory = new_entries.iter().map(|e| e.0.clone()).collect();
+        InventoryDelta(bazaar::inventory_delta::InventoryDelta::between(&old, &new))
+    }
+
+    /// Apply this delta to `entries` (every [`InventoryEntry`] of the base
+    /// inventory), returning the entries of the resulting inventory.
+    fn apply_to(
+        &self,
+        py: Python,
+        entries: Vec<PyRef<InventoryEntry>>,
+    ) -> PyResult<Vec<PyObject>> {
+        apply_inventory_delta_to_entries(py, entries, &self.0)
     }
 
     fn __repr__(&self) -> String {
@@ -942,6 +973,80 @@ impl InventoryDelta {
     }
 }
 
+fn inconsistency_to_err(e: InventoryDeltaInconsistency) -> PyErr {
+    match e {
+        InventoryDeltaInconsistency::NoPath => {
+            InconsistentDelta::new_err(("", "", "No path in entry"))
+        }
+        InventoryDeltaInconsistency::DuplicateFileId(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "repeated file_id"))
+        }
+        InventoryDeltaInconsistency::DuplicateOldPath(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "repeated path"))
+        }
+        InventoryDeltaInconsistency::DuplicateNewPath(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "repeated path"))
+        }
+        InventoryDeltaInconsistency::MismatchedId(path, fid1, fid2) => InconsistentDelta::new_err((
+            path,
+            fid1.bytes().to_vec(),
+            format!("mismatched id with entry {}", fid2),
+        )),
+        InventoryDeltaInconsistency::EntryWithoutPath(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "Entry with no new_path"))
+        }
+        InventoryDeltaInconsistency::PathWithoutEntry(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "new_path with no entry"))
+        }
+        InventoryDeltaInconsistency::MissingParent(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "missing parent"))
+        }
+        InventoryDeltaInconsistency::InvalidName(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "invalid name"))
+        }
+        InventoryDeltaInconsistency::NonNormalizedName(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "name not normalized"))
+        }
+        InventoryDeltaInconsistency::MissingTextSize(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "missing text_size"))
+        }
+        InventoryDeltaInconsistency::MissingSymlinkTarget(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "missing symlink target"))
+        }
+        InventoryDeltaInconsistency::UnknownId(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "unknown file_id"))
+        }
+        InventoryDeltaInconsistency::PathCollision(path, fid) => {
+            InconsistentDelta::new_err((path, fid.bytes().to_vec(), "path occupied by another file_id"))
+        }
+    }
+}
+
+fn apply_inventory_delta_to_entries(
+    py: Python,
+    entries: Vec<PyRef<InventoryEntry>>,
+    delta: &bazaar::inventory_delta::InventoryDelta,
+) -> PyResult<Vec<PyObject>> {
+    let base: bazaar::inventory::Inventory = entries.iter().map(|e| e.0.clone()).collect();
+    let result = delta.apply(&base).map_err(inconsistency_to_err)?;
+    result
+        .iter()
+        .map(|e| entry_to_py(py, e.clone()))
+        .collect()
+}
+
+/// Apply `delta` to the base inventory given as `entries` (every
+/// [`InventoryEntry`] of it), returning the entries of the resulting
+/// inventory.
+#[pyfunction]
+fn apply_inventory_delta(
+    py: Python,
+    entries: Vec<PyRef<InventoryEntry>>,
+    delta: &InventoryDelta,
+) -> PyResult<Vec<PyObject>> {
+    apply_inventory_delta_to_entries(py, entries, &delta.0)
+}
+
 #[pyfunction]
 fn parse_inventory_delta(
     py: Python,
@@ -972,6 +1077,50 @@ fn parse_inventory_delta(
     Ok((parent, version, versioned_root, tree_references, result))
 }
 
+/// Guarded variant of [`parse_inventory_delta`] for payloads arriving over
+/// the smart protocol: `payload` must be exactly `declared_len` bytes and
+/// hash to `checksum` (the FNV-1a content checksum also used by the
+/// binary inventory format), or parsing aborts with `InventoryDeltaError`
+/// rather than silently accepting a truncated delta.
+#[pyfunction]
+fn parse_inventory_delta_guarded(
+    py: Python,
+    payload: Vec<u8>,
+    declared_len: u64,
+    checksum: u64,
+    allow_versioned_root: Option<bool>,
+    allow_tree_references: Option<bool>,
+) -> PyResult<(PyObject, PyObject, bool, bool, PyObject)> {
+    let mut reader = std::io::Cursor::new(payload);
+    let (parent, version, versioned_root, tree_references, result) =
+        bazaar::inventory_delta::parse_inventory_delta_from_reader(
+            &mut reader,
+            declared_len,
+            checksum,
+            allow_versioned_root,
+            allow_tree_references,
+        )
+        .map_err(|e| match e {
+            InventoryDeltaParseError::Invalid(m) => InventoryDeltaError::new_err((m,)),
+            InventoryDeltaParseError::Incompatible(m) => IncompatibleInventoryDelta::new_err((m,)),
+            InventoryDeltaParseError::LengthMismatch { declared, actual } => {
+                InventoryDeltaError::new_err((format!(
+                    "declared length {} but read {} bytes",
+                    declared, actual
+                ),))
+            }
+            InventoryDeltaParseError::ChecksumMismatch => {
+                InventoryDeltaError::new_err(("content checksum mismatch",))
+            }
+        })?;
+
+    let parent = PyBytes::new(py, parent.bytes()).to_object(py);
+    let version = PyBytes::new(py, version.bytes()).to_object(py);
+    let result = PyCell::new(py, InventoryDelta(result))?.to_object(py);
+
+    Ok((parent, version, versioned_root, tree_references, result))
+}
+
 #[pyfunction]
 fn parse_inventory_entry(
     file_id: Vec<u8>,
@@ -1028,6 +1177,232 @@ fn serialize_inventory_delta(
     .collect())
 }
 
+/// Check every entry of an inventory in a single native pass, instead of
+/// the per-entry `InventoryEntry.check()`/`common_ie_check` round-trips.
+///
+/// `entries` is every [`InventoryEntry`] in the inventory being checked
+/// (including the root). Pending text-verification items are handed to
+/// `checker.add_pending_item` first, then inconsistencies found are
+/// reported via `checker._report_items`/raised as `BzrCheckError`,
+/// matching the wording `common_ie_check` already uses.
+#[pyfunction]
+fn check_inventory(
+    py: Python,
+    entries: Vec<PyRef<InventoryEntry>>,
+    rev_id: Vec<u8>,
+    rich_roots: bool,
+    checker: PyObject,
+) -> PyResult<()> {
+    let revision_id = RevisionId::from(rev_id.clone());
+    let inventory: bazaar::inventory::Inventory =
+        entries.iter().map(|e| e.0.clone()).collect();
+
+    let report = bazaar::inventory_check::check_inventory(&revision_id, &inventory, rich_roots);
+
+    // Hand every pending text-verification item to the checker before
+    // acting on any inconsistency below, so a hard error partway through
+    // the inventory doesn't discard work already accumulated for entries
+    // that were otherwise fine.
+    for item in report.pending_items {
+        checker.call_method1(
+            py,
+            "add_pending_item",
+            (
+                PyBytes::new(py, item.revision_id.bytes()).to_object(py),
+                (
+                    "texts",
+                    PyBytes::new(py, item.text_key.0.bytes()).to_object(py),
+                    item.text_key
+                        .1
+                        .as_ref()
+                        .map(|r| PyBytes::new(py, r.bytes()).to_object(py)),
+                ),
+                PyBytes::new(py, item.kind.as_bytes()).to_object(py),
+                PyBytes::new(py, item.sha1.as_slice()).to_object(py),
+            ),
+        )?;
+    }
+
+    for inconsistency in report.inconsistencies {
+        match inconsistency {
+            InventoryDeltaInconsistency::MissingParent(_, fid) => {
+                let parent_id = inventory
+                    .get(&fid)
+                    .and_then(|e| e.parent_id())
+                    .cloned()
+                    .unwrap_or_else(|| fid.clone());
+                return Err(BzrCheckError::new_err(format!(
+                    "missing parent {{{}}} in inventory for revision {{{}}}",
+                    parent_id,
+                    String::from_utf8_lossy(&rev_id)
+                )));
+            }
+            InventoryDeltaInconsistency::MissingTextSize(_, fid) => {
+                checker.getattr(py, "_report_items")?.call_method1(
+                    py,
+                    "append",
+                    (format!(
+                        "fileid {{{}}} in {{{}}} has None for text_size",
+                        fid,
+                        String::from_utf8_lossy(&rev_id)
+                    ),),
+                )?;
+            }
+            InventoryDeltaInconsistency::InvalidName(name, fid) => {
+                return Err(InvalidEntryName::new_err((format!(
+                    "entry {{{}}} has invalid name {:?}",
+                    fid, name
+                ),)));
+            }
+            InventoryDeltaInconsistency::NonNormalizedName(name, _fid) => {
+                return Err(InvalidNormalization::new_err(name));
+            }
+            InventoryDeltaInconsistency::MissingSymlinkTarget(_, fid) => {
+                checker.getattr(py, "_report_items")?.call_method1(
+                    py,
+                    "append",
+                    (format!(
+                        "fileid {{{}}} in {{{}}} has no symlink target",
+                        fid,
+                        String::from_utf8_lossy(&rev_id)
+                    ),),
+                )?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Compact binary counterpart to [`serialize_inventory_delta`]: a short
+/// magic + version header followed by length-prefixed, escaping-free
+/// records, cheaper to produce and parse for deltas with arbitrary-byte
+/// file_ids or paths.
+#[pyfunction]
+fn serialize_inventory_delta_binary(
+    py: Python,
+    old_name: Vec<u8>,
+    new_name: Vec<u8>,
+    delta_to_new: &InventoryDelta,
+    versioned_root: bool,
+    tree_references: bool,
+) -> PyResult<PyObject> {
+    Ok(PyBytes::new(
+        py,
+        &bazaar::inventory_delta::serialize_inventory_delta_binary(
+            &RevisionId::from(old_name),
+            &RevisionId::from(new_name),
+            &delta_to_new.0,
+            versioned_root,
+            tree_references,
+        )
+        .map_err(|e| match e {
+            InventoryDeltaSerializeError::Invalid(m) => InventoryDeltaError::new_err((m,)),
+            InventoryDeltaSerializeError::UnsupportedKind(k) => PyKeyError::new_err((k,)),
+        })?,
+    )
+    .to_object(py))
+}
+
+/// Parse the binary wire format produced by
+/// [`serialize_inventory_delta_binary`].
+#[pyfunction]
+fn parse_inventory_delta_binary(
+    py: Python,
+    data: Vec<u8>,
+    allow_versioned_root: Option<bool>,
+    allow_tree_references: Option<bool>,
+) -> PyResult<(PyObject, PyObject, bool, bool, PyObject)> {
+    let (parent, version, versioned_root, tree_references, result) =
+        bazaar::inventory_delta::parse_inventory_delta_binary(
+            &data,
+            allow_versioned_root,
+            allow_tree_references,
+        )
+        .map_err(|e| match e {
+            InventoryDeltaParseError::Invalid(m) => InventoryDeltaError::new_err((m,)),
+            InventoryDeltaParseError::Incompatible(m) => IncompatibleInventoryDelta::new_err((m,)),
+            InventoryDeltaParseError::LengthMismatch { declared, actual } => {
+                InventoryDeltaError::new_err((format!(
+                    "declared length {} but read {} bytes",
+                    declared, actual
+                ),))
+            }
+            InventoryDeltaParseError::ChecksumMismatch => {
+                InventoryDeltaError::new_err(("content checksum mismatch",))
+            }
+        })?;
+
+    let parent = PyBytes::new(py, parent.bytes()).to_object(py);
+    let version = PyBytes::new(py, version.bytes()).to_object(py);
+    let result = PyCell::new(py, InventoryDelta(result))?.to_object(py);
+
+    Ok((parent, version, versioned_root, tree_references, result))
+}
+
+/// Serialize `entries` (every entry in the inventory, including the root)
+/// into the zero-copy binary format read by [`parse_inventory_binary`] and
+/// [`get_inventory_binary_entry`].
+#[pyfunction]
+fn serialize_inventory_binary(
+    py: Python,
+    root_file_id: Vec<u8>,
+    entries: Vec<PyRef<InventoryEntry>>,
+) -> PyResult<PyObject> {
+    let entries: Vec<Entry> = entries.iter().map(|e| e.0.clone()).collect();
+    let data =
+        bazaar::inventory_binary::write_inventory_binary(&FileId::from(root_file_id), &entries)
+            .map_err(|e| match e {
+                InventoryBinaryWriteError::TextSha1TooLong(len) => {
+                    InventoryBinaryFormatError::new_err((format!(
+                        "text_sha1 is {} bytes, too long for the binary format",
+                        len
+                    ),))
+                }
+            })?;
+    Ok(PyBytes::new(py, data.as_slice()).to_object(py))
+}
+
+fn binary_format_err(e: InventoryBinaryError) -> PyErr {
+    InventoryBinaryFormatError::new_err((e.to_string(),))
+}
+
+/// Parse the binary wire format produced by [`serialize_inventory_binary`],
+/// decoding every entry.
+///
+/// For a single lookup, prefer [`get_inventory_binary_entry`], which avoids
+/// decoding entries other than the one requested.
+#[pyfunction]
+fn parse_inventory_binary(py: Python, data: Vec<u8>) -> PyResult<(PyObject, Vec<PyObject>)> {
+    let reader = InventoryReader::parse(&data).map_err(binary_format_err)?;
+    let root_file_id = PyBytes::new(py, reader.root_file_id.bytes()).to_object(py);
+    let entries = reader
+        .iter_entries()
+        .map(|entry| entry_to_py(py, entry.map_err(binary_format_err)?))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok((root_file_id, entries))
+}
+
+/// Look up a single entry in the binary wire format by file_id, via a
+/// binary search over the file_id-sorted record array, without decoding
+/// any other entry.
+#[pyfunction]
+fn get_inventory_binary_entry(
+    py: Python,
+    data: Vec<u8>,
+    file_id: Vec<u8>,
+) -> PyResult<Option<PyObject>> {
+    let reader = InventoryReader::parse(&data).map_err(binary_format_err)?;
+    match reader
+        .get_entry(&FileId::from(file_id))
+        .map_err(binary_format_err)?
+    {
+        Some(entry) => Ok(Some(entry_to_py(py, entry)?)),
+        None => Ok(None),
+    }
+}
+
 pub fn _inventory_rs(py: Python) -> PyResult<&PyModule> {
     let m = PyModule::new(py, "inventory")?;
 
@@ -1041,14 +1416,26 @@ pub fn _inventory_rs(py: Python) -> PyResult<&PyModule> {
     m.add_wrapped(wrap_pyfunction!(ensure_normalized_name))?;
     m.add_class::<InventoryDelta>()?;
     m.add_wrapped(wrap_pyfunction!(parse_inventory_delta))?;
+    m.add_wrapped(wrap_pyfunction!(parse_inventory_delta_guarded))?;
     m.add_wrapped(wrap_pyfunction!(parse_inventory_entry))?;
     m.add_wrapped(wrap_pyfunction!(serialize_inventory_delta))?;
+    m.add_wrapped(wrap_pyfunction!(serialize_inventory_delta_binary))?;
+    m.add_wrapped(wrap_pyfunction!(parse_inventory_delta_binary))?;
     m.add_wrapped(wrap_pyfunction!(serialize_inventory_entry))?;
+    m.add_wrapped(wrap_pyfunction!(check_inventory))?;
+    m.add_wrapped(wrap_pyfunction!(apply_inventory_delta))?;
+    m.add_wrapped(wrap_pyfunction!(serialize_inventory_binary))?;
+    m.add_wrapped(wrap_pyfunction!(parse_inventory_binary))?;
+    m.add_wrapped(wrap_pyfunction!(get_inventory_binary_entry))?;
     m.add("InventoryDeltaError", py.get_type::<InventoryDeltaError>())?;
     m.add(
         "IncompatibleInventoryDelta",
         py.get_type::<IncompatibleInventoryDelta>(),
     )?;
+    m.add(
+        "InventoryBinaryFormatError",
+        py.get_type::<InventoryBinaryFormatError>(),
+    )?;
 
     Ok(m)
 }
@@ -0,0 +1,410 @@
+use crate::{FileId, RevisionId};
+use breezy_osutils::Kind;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// A single entry in an inventory: a file, directory, symlink, or
+/// tree-reference that is or was versioned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    File {
+        file_id: FileId,
+        name: String,
+        parent_id: Option<FileId>,
+        revision: Option<RevisionId>,
+        text_sha1: Option<Vec<u8>>,
+        text_size: Option<u64>,
+        text_id: Option<Vec<u8>>,
+        executable: bool,
+    },
+    Directory {
+        file_id: FileId,
+        name: String,
+        parent_id: Option<FileId>,
+        revision: Option<RevisionId>,
+    },
+    TreeReference {
+        file_id: FileId,
+        name: String,
+        parent_id: Option<FileId>,
+        revision: Option<RevisionId>,
+        reference_revision: Option<RevisionId>,
+    },
+    Link {
+        file_id: FileId,
+        name: String,
+        parent_id: Option<FileId>,
+        symlink_target: Option<String>,
+        revision: Option<RevisionId>,
+    },
+}
+
+impl Entry {
+    pub fn new(kind: Kind, name: String, file_id: FileId, parent_id: Option<FileId>) -> Self {
+        match kind {
+            Kind::File => Entry::File {
+                file_id,
+                name,
+                parent_id,
+                revision: None,
+                text_sha1: None,
+                text_size: None,
+                text_id: None,
+                executable: false,
+            },
+            Kind::Directory => Entry::Directory {
+                file_id,
+                name,
+                parent_id,
+                revision: None,
+            },
+            Kind::TreeReference => Entry::TreeReference {
+                file_id,
+                name,
+                parent_id,
+                revision: None,
+                reference_revision: None,
+            },
+            Kind::Symlink => Entry::Link {
+                file_id,
+                name,
+                parent_id,
+                symlink_target: None,
+                revision: None,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Entry::File { .. } => Kind::File,
+            Entry::Directory { .. } => Kind::Directory,
+            Entry::TreeReference { .. } => Kind::TreeReference,
+            Entry::Link { .. } => Kind::Symlink,
+        }
+    }
+
+    pub fn file_id(&self) -> &FileId {
+        match self {
+            Entry::File { file_id, .. }
+            | Entry::Directory { file_id, .. }
+            | Entry::TreeReference { file_id, .. }
+            | Entry::Link { file_id, .. } => file_id,
+        }
+    }
+
+    pub fn parent_id(&self) -> Option<&FileId> {
+        match self {
+            Entry::File { parent_id, .. }
+            | Entry::Directory { parent_id, .. }
+            | Entry::TreeReference { parent_id, .. }
+            | Entry::Link { parent_id, .. } => parent_id.as_ref(),
+        }
+    }
+
+    pub fn revision(&self) -> Option<&RevisionId> {
+        match self {
+            Entry::File { revision, .. }
+            | Entry::Directory { revision, .. }
+            | Entry::TreeReference { revision, .. }
+            | Entry::Link { revision, .. } => revision.as_ref(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Entry::File { name, .. }
+            | Entry::Directory { name, .. }
+            | Entry::TreeReference { name, .. }
+            | Entry::Link { name, .. } => name,
+        }
+    }
+
+    pub fn symlink_target(&self) -> Option<&str> {
+        match self {
+            Entry::Link { symlink_target, .. } => symlink_target.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether `self` and `other` are different views of the same revision
+    /// of this file (i.e. neither text nor metadata changed).
+    pub fn is_unmodified(&self, other: &Entry) -> bool {
+        self.revision().is_some() && self.revision() == other.revision()
+    }
+
+    /// Whether `self` and `other` describe exactly the same entry.
+    pub fn unchanged(&self, other: &Entry) -> bool {
+        self == other
+    }
+}
+
+/// An in-memory collection of [`Entry`] values keyed by file_id, the unit
+/// that [`crate::inventory_check::check_inventory`] walks in a single pass.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    by_id: std::collections::HashMap<FileId, Entry>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, entry: Entry) {
+        self.by_id.insert(entry.file_id().clone(), entry);
+    }
+
+    pub fn get(&self, file_id: &FileId) -> Option<&Entry> {
+        self.by_id.get(file_id)
+    }
+
+    pub fn has_id(&self, file_id: &FileId) -> bool {
+        self.by_id.contains_key(file_id)
+    }
+
+    pub fn remove(&mut self, file_id: &FileId) -> Option<Entry> {
+        self.by_id.remove(file_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.by_id.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+}
+
+impl FromIterator<Entry> for Inventory {
+    fn from_iter<I: IntoIterator<Item = Entry>>(iter: I) -> Self {
+        let mut inv = Inventory::new();
+        for entry in iter {
+            inv.insert(entry);
+        }
+        inv
+    }
+}
+
+/// Whether entries of `kind` can be placed under version control at all.
+pub fn versionable_kind(kind: Kind) -> bool {
+    matches!(
+        kind,
+        Kind::File | Kind::Directory | Kind::Symlink | Kind::TreeReference
+    )
+}
+
+/// Whether `name` is a legal single path component for an inventory entry:
+/// non-empty, free of path separators, and not `.`/`..`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\\')
+}
+
+/// Check that `path`'s final component is already Unicode-NFC normalized,
+/// returning it unchanged if so.
+pub fn ensure_normalized_name(path: &std::path::Path) -> Result<std::path::PathBuf, ()> {
+    use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+    let name = path.to_string_lossy();
+    if is_nfc(&name) {
+        Ok(path.to_path_buf())
+    } else {
+        let _ = name.nfc().collect::<String>();
+        Err(())
+    }
+}
+
+/// Report whether the text and/or metadata (e.g. the executable bit,
+/// symlink target) differ between two entries for the same file_id.
+pub fn detect_changes(old: &Entry, new: &Entry) -> (bool, bool) {
+    match (old, new) {
+        (
+            Entry::File {
+                text_sha1: old_sha1,
+                executable: old_exec,
+                ..
+            },
+            Entry::File {
+                text_sha1: new_sha1,
+                executable: new_exec,
+                ..
+            },
+        ) => (old_sha1 != new_sha1, old_exec != new_exec),
+        (
+            Entry::Link {
+                symlink_target: old_target,
+                ..
+            },
+            Entry::Link {
+                symlink_target: new_target,
+                ..
+            },
+        ) => (old_target != new_target, false),
+        _ => (false, false),
+    }
+}
+
+/// A revision queued for expansion in [`heads`], ordered by its distance
+/// from the original candidate set rather than by `RevisionId`'s byte
+/// ordering (which has no relationship to the revision graph).
+struct FrontierEntry {
+    /// Steps from the nearest original candidate; 0 for the candidates
+    /// themselves. Smaller is topologically newer.
+    depth: u32,
+    revision: RevisionId,
+}
+
+impl PartialEq for FrontierEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.depth == other.depth && self.revision == other.revision
+    }
+}
+impl Eq for FrontierEntry {}
+
+impl PartialOrd for FrontierEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FrontierEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap, and we want the shallowest (newest)
+        // entries popped first, so invert the depth comparison; break ties
+        // on the revision id purely to keep the ordering total.
+        other
+            .depth
+            .cmp(&self.depth)
+            .then_with(|| self.revision.cmp(&other.revision))
+    }
+}
+
+/// Reduce a set of candidate revisions for a per-file graph down to its
+/// heads: the candidates that are not themselves an ancestor of another
+/// candidate.
+///
+/// `get_parent_map` is called with the single revision being expanded and
+/// should return the known parents for it (if any). Revisions are expanded
+/// topologically-newest-first via a priority queue ordered by distance from
+/// the original candidates, tracking a `seen` set so each one is only
+/// walked once; whenever a walk reaches a revision that is itself a
+/// candidate, that candidate is dropped from the surviving head set. The
+/// walk stops as soon as at most one candidate remains, since the frontier
+/// can no longer exclude anything further at that point.
+pub fn heads<F>(candidates: &[RevisionId], mut get_parent_map: F) -> HashSet<RevisionId>
+where
+    F: FnMut(&RevisionId) -> Vec<RevisionId>,
+{
+    let mut surviving: HashSet<RevisionId> = candidates.iter().cloned().collect();
+    let mut seen: HashSet<RevisionId> = HashSet::new();
+    let mut frontier: BinaryHeap<FrontierEntry> = candidates
+        .iter()
+        .cloned()
+        .map(|revision| FrontierEntry { depth: 0, revision })
+        .collect();
+
+    while surviving.len() > 1 {
+        let Some(FrontierEntry { depth, revision }) = frontier.pop() else {
+            break;
+        };
+        if !seen.insert(revision.clone()) {
+            continue;
+        }
+        for parent in get_parent_map(&revision) {
+            surviving.remove(&parent);
+            if !seen.contains(&parent) {
+                frontier.push(FrontierEntry {
+                    depth: depth + 1,
+                    revision: parent,
+                });
+            }
+        }
+    }
+
+    surviving
+}
+
+/// A short human-readable description of the change between two optional
+/// entries, as used in `bzr status`-style output.
+pub fn describe_change(old: Option<&Entry>, new: Option<&Entry>) -> &'static str {
+    match (old, new) {
+        (None, None) => "unchanged",
+        (None, Some(_)) => "added",
+        (Some(_), None) => "removed",
+        (Some(old), Some(new)) => {
+            if old.kind() != new.kind() {
+                "kind changed"
+            } else if old.name() != new.name() || old.parent_id() != new.parent_id() {
+                "renamed"
+            } else {
+                let (text_modified, meta_modified) = detect_changes(old, new);
+                if text_modified || meta_modified {
+                    "modified"
+                } else {
+                    "unchanged"
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<RevisionId, Vec<RevisionId>> {
+        edges
+            .iter()
+            .map(|(rev, parents)| {
+                (
+                    RevisionId::from(rev.as_bytes()),
+                    parents
+                        .iter()
+                        .map(|p| RevisionId::from(p.as_bytes()))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn rev(name: &str) -> RevisionId {
+        RevisionId::from(name.as_bytes())
+    }
+
+    #[test]
+    fn heads_drops_an_ancestor_of_another_candidate() {
+        // b1 -> a1, so of the two candidates only b1 is a head.
+        let parents = graph(&[("b1", &["a1"]), ("a1", &[])]);
+        let candidates = [rev("a1"), rev("b1")];
+
+        let result = heads(&candidates, |r| {
+            parents.get(r).cloned().unwrap_or_default()
+        });
+
+        assert_eq!(result, [rev("b1")].into_iter().collect());
+    }
+
+    #[test]
+    fn heads_keeps_unrelated_candidates() {
+        let parents = graph(&[("a1", &[]), ("b1", &[])]);
+        let candidates = [rev("a1"), rev("b1")];
+
+        let result = heads(&candidates, |r| {
+            parents.get(r).cloned().unwrap_or_default()
+        });
+
+        assert_eq!(result, candidates.into_iter().collect());
+    }
+
+    #[test]
+    fn heads_of_a_single_candidate_is_itself() {
+        let result = heads(&[rev("a1")], |_| vec![]);
+
+        assert_eq!(result, [rev("a1")].into_iter().collect());
+    }
+}
@@ -0,0 +1,801 @@
+//! A zero-copy, mmap-friendly binary inventory format.
+//!
+//! Unlike the text inventory-delta format in [`crate::inventory_delta`],
+//! which requires materializing every [`Entry`] up front, this format is
+//! modeled on Mercurial's dirstate-v2 layout: a small fixed "docket" header
+//! points at a data blob made up of fixed-size records (one per inventory
+//! entry) plus an appended string table. Records are parsed with unaligned
+//! big-endian integer views so a borrowed `&[u8]` yields an [`Entry`]
+//! without allocating, and [`InventoryReader::get_entry`] binary-searches a
+//! file_id-sorted record array to look up a single entry without touching
+//! the rest of the file. [`write_inventory_binary`] is the inverse:
+//! it takes a flat list of entries and produces the docket-prefixed bytes
+//! [`InventoryReader::parse`] expects.
+
+use crate::inventory::Entry;
+use crate::FileId;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"BINV";
+const FORMAT_VERSION: u8 = 1;
+
+/// Size in bytes of a single fixed-size entry record (see [`Record`]).
+const RECORD_SIZE: usize = 61;
+
+/// Unaligned, big-endian `u16` view over a byte slice.
+#[derive(Debug, Clone, Copy)]
+pub struct U16Be([u8; 2]);
+
+impl U16Be {
+    pub fn get(self) -> u16 {
+        u16::from_be_bytes(self.0)
+    }
+}
+
+/// Unaligned, big-endian `u32` view over a byte slice.
+#[derive(Debug, Clone, Copy)]
+pub struct U32Be([u8; 4]);
+
+impl U32Be {
+    pub fn get(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+}
+
+/// Unaligned, big-endian `u64` view over a byte slice.
+#[derive(Debug, Clone, Copy)]
+pub struct U64Be([u8; 8]);
+
+impl U64Be {
+    pub fn get(self) -> u64 {
+        u64::from_be_bytes(self.0)
+    }
+}
+
+/// Types that can be reinterpreted from a byte slice without copying or
+/// requiring alignment.
+pub trait BytesCast: Sized + Copy {
+    const SIZE: usize;
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_bytes_cast {
+    ($ty:ty, $size:expr) => {
+        impl BytesCast for $ty {
+            const SIZE: usize = $size;
+            fn from_bytes(bytes: &[u8]) -> Option<Self> {
+                let array: [u8; $size] = bytes.get(..$size)?.try_into().ok()?;
+                Some(Self(array))
+            }
+        }
+    };
+}
+
+impl_bytes_cast!(U16Be, 2);
+impl_bytes_cast!(U32Be, 4);
+impl_bytes_cast!(U64Be, 8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKindTag {
+    File = 1,
+    Directory = 2,
+    TreeReference = 3,
+    Symlink = 4,
+}
+
+impl EntryKindTag {
+    fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(EntryKindTag::File),
+            2 => Some(EntryKindTag::Directory),
+            3 => Some(EntryKindTag::TreeReference),
+            4 => Some(EntryKindTag::Symlink),
+            _ => None,
+        }
+    }
+}
+
+/// A borrowed view over one fixed-size record in the data region.
+struct Record<'a> {
+    bytes: &'a [u8; RECORD_SIZE],
+}
+
+impl<'a> Record<'a> {
+    fn kind_tag(&self) -> Option<EntryKindTag> {
+        EntryKindTag::from_u8(self.bytes[0])
+    }
+
+    fn field(&self, offset: usize) -> (u32, u16) {
+        let off = U32Be::from_bytes(&self.bytes[offset..]).unwrap().get();
+        let len = U16Be::from_bytes(&self.bytes[offset + 4..]).unwrap().get();
+        (off, len)
+    }
+
+    fn name(&self) -> (u32, u16) {
+        self.field(1)
+    }
+    fn file_id(&self) -> (u32, u16) {
+        self.field(7)
+    }
+    fn parent_id(&self) -> (u32, u16) {
+        self.field(13)
+    }
+    fn revision(&self) -> (u32, u16) {
+        self.field(19)
+    }
+    /// `symlink_target` for symlinks, `reference_revision` for tree
+    /// references; unused for files/directories.
+    fn extra(&self) -> (u32, u16) {
+        self.field(25)
+    }
+    fn text_size(&self) -> Option<u64> {
+        let v = U64Be::from_bytes(&self.bytes[31..]).unwrap().get();
+        if v == u64::MAX {
+            None
+        } else {
+            Some(v)
+        }
+    }
+    fn executable(&self) -> bool {
+        self.bytes[39] != 0
+    }
+    fn text_sha1(&self) -> Result<Option<&'a [u8]>, InventoryBinaryError> {
+        let len = self.bytes[40] as usize;
+        if len == 0 {
+            Ok(None)
+        } else if len > TEXT_SHA1_CAPACITY {
+            Err(InventoryBinaryError::InvalidTextSha1Length(len))
+        } else {
+            Ok(Some(&self.bytes[41..41 + len]))
+        }
+    }
+}
+
+/// Number of bytes a record reserves for an inline `text_sha1` (see
+/// [`Record::text_sha1`]); a SHA-1 digest is always exactly 20 bytes.
+const TEXT_SHA1_CAPACITY: usize = RECORD_SIZE - 41;
+
+/// Errors that can occur while parsing a binary inventory file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryBinaryError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The data region's actual length didn't match the docket's declared
+    /// length. Since every record offset is trusted relative to this
+    /// region, a mismatch here must be rejected before any other parsing is
+    /// attempted.
+    LengthMismatch { declared: u32, actual: usize },
+    ChecksumMismatch,
+    OffsetOutOfRange,
+    /// A record's inline `text_sha1` length byte exceeds
+    /// [`TEXT_SHA1_CAPACITY`], the most the fixed-size record can hold.
+    InvalidTextSha1Length(usize),
+}
+
+impl std::fmt::Display for InventoryBinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryBinaryError::Truncated => write!(f, "truncated inventory file"),
+            InventoryBinaryError::BadMagic => write!(f, "not a binary inventory file"),
+            InventoryBinaryError::UnsupportedVersion(v) => {
+                write!(f, "unsupported binary inventory format version {}", v)
+            }
+            InventoryBinaryError::LengthMismatch { declared, actual } => write!(
+                f,
+                "data region length {} does not match docket-declared length {}",
+                actual, declared
+            ),
+            InventoryBinaryError::ChecksumMismatch => {
+                write!(f, "data region checksum does not match docket")
+            }
+            InventoryBinaryError::OffsetOutOfRange => {
+                write!(f, "record references an offset outside of the data region")
+            }
+            InventoryBinaryError::InvalidTextSha1Length(len) => write!(
+                f,
+                "record declares a text_sha1 length of {} bytes, more than the {} the binary format reserves",
+                len, TEXT_SHA1_CAPACITY
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InventoryBinaryError {}
+
+/// A cheap, non-cryptographic checksum of the data region, stored in the
+/// docket so a truncated or corrupted file is rejected before any record
+/// offsets are trusted.
+pub(crate) fn content_checksum(data: &[u8]) -> u64 {
+    // FNV-1a.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// A parsed binary inventory file: the docket header plus a borrowed view
+/// over its data region (records + string table).
+///
+/// Construction validates the docket up front (magic, version, and that the
+/// data region's actual length matches the docket's declared length) but
+/// does not eagerly parse any entry; [`get_entry`](Self::get_entry) does
+/// that lazily, on demand, without allocating.
+pub struct InventoryReader<'a> {
+    pub root_file_id: FileId,
+    entry_count: u32,
+    data: &'a [u8],
+}
+
+impl<'a> InventoryReader<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, InventoryBinaryError> {
+        if bytes.len() < 4 + 1 + 2 {
+            return Err(InventoryBinaryError::Truncated);
+        }
+        if &bytes[0..4] != MAGIC {
+            return Err(InventoryBinaryError::BadMagic);
+        }
+        let version = bytes[4];
+        if version != FORMAT_VERSION {
+            return Err(InventoryBinaryError::UnsupportedVersion(version));
+        }
+
+        let mut pos = 5usize;
+        let root_file_id_len =
+            U16Be::from_bytes(bytes.get(pos..).ok_or(InventoryBinaryError::Truncated)?)
+                .ok_or(InventoryBinaryError::Truncated)?
+                .get() as usize;
+        pos += 2;
+        let root_file_id = FileId::from(
+            bytes
+                .get(pos..pos + root_file_id_len)
+                .ok_or(InventoryBinaryError::Truncated)?
+                .to_vec(),
+        );
+        pos += root_file_id_len;
+
+        let entry_count =
+            U32Be::from_bytes(bytes.get(pos..).ok_or(InventoryBinaryError::Truncated)?)
+                .ok_or(InventoryBinaryError::Truncated)?
+                .get();
+        pos += 4;
+
+        let data_len =
+            U32Be::from_bytes(bytes.get(pos..).ok_or(InventoryBinaryError::Truncated)?)
+                .ok_or(InventoryBinaryError::Truncated)?
+                .get();
+        pos += 4;
+
+        let declared_checksum =
+            U64Be::from_bytes(bytes.get(pos..).ok_or(InventoryBinaryError::Truncated)?)
+                .ok_or(InventoryBinaryError::Truncated)?
+                .get();
+        pos += 8;
+
+        let data = bytes.get(pos..).ok_or(InventoryBinaryError::Truncated)?;
+
+        // The data region's length drives every offset below: reject
+        // truncated or over-long files before trusting any of them.
+        if data.len() != data_len as usize {
+            return Err(InventoryBinaryError::LengthMismatch {
+                declared: data_len,
+                actual: data.len(),
+            });
+        }
+
+        if content_checksum(data) != declared_checksum {
+            return Err(InventoryBinaryError::ChecksumMismatch);
+        }
+
+        if data.len() < entry_count as usize * RECORD_SIZE {
+            return Err(InventoryBinaryError::Truncated);
+        }
+
+        Ok(InventoryReader {
+            root_file_id,
+            entry_count,
+            data,
+        })
+    }
+
+    fn record(&self, index: usize) -> Option<Record<'_>> {
+        let start = index * RECORD_SIZE;
+        let bytes: &[u8; RECORD_SIZE] = self.data.get(start..start + RECORD_SIZE)?.try_into().ok()?;
+        Some(Record { bytes })
+    }
+
+    fn string_table_slice(&self, offset: u32, len: u16) -> Option<&[u8]> {
+        let start = self.entry_count as usize * RECORD_SIZE + offset as usize;
+        self.data.get(start..start + len as usize)
+    }
+
+    fn record_file_id(&self, index: usize) -> Option<&[u8]> {
+        let record = self.record(index)?;
+        let (off, len) = record.file_id();
+        self.string_table_slice(off, len)
+    }
+
+    /// Look up a single entry by file_id via binary search over the
+    /// file_id-sorted record array, without deserializing any other entry.
+    pub fn get_entry(&self, file_id: &FileId) -> Result<Option<Entry>, InventoryBinaryError> {
+        let mut lo = 0usize;
+        let mut hi = self.entry_count as usize;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let candidate = self
+                .record_file_id(mid)
+                .ok_or(InventoryBinaryError::OffsetOutOfRange)?;
+            match candidate.cmp(file_id.bytes()) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return self.build_entry(mid).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The number of entries in the file.
+    pub fn len(&self) -> usize {
+        self.entry_count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Decode every entry, in file_id order.
+    pub fn iter_entries(&self) -> impl Iterator<Item = Result<Entry, InventoryBinaryError>> + '_ {
+        (0..self.entry_count as usize).map(move |index| self.build_entry(index))
+    }
+
+    fn build_entry(&self, index: usize) -> Result<Entry, InventoryBinaryError> {
+        let record = self
+            .record(index)
+            .ok_or(InventoryBinaryError::OffsetOutOfRange)?;
+        let kind = record
+            .kind_tag()
+            .ok_or(InventoryBinaryError::OffsetOutOfRange)?;
+
+        let get = |field: (u32, u16)| -> Result<Vec<u8>, InventoryBinaryError> {
+            self.string_table_slice(field.0, field.1)
+                .map(|s| s.to_vec())
+                .ok_or(InventoryBinaryError::OffsetOutOfRange)
+        };
+
+        let name = String::from_utf8_lossy(&get(record.name())?).into_owned();
+        let file_id = FileId::from(get(record.file_id())?);
+        let (parent_off, parent_len) = record.parent_id();
+        let parent_id = if parent_len == 0 {
+            None
+        } else {
+            Some(FileId::from(get((parent_off, parent_len))?))
+        };
+        let (rev_off, rev_len) = record.revision();
+        let revision = if rev_len == 0 {
+            None
+        } else {
+            Some(crate::RevisionId::from(get((rev_off, rev_len))?))
+        };
+
+        Ok(match kind {
+            EntryKindTag::Directory => Entry::Directory {
+                file_id,
+                name,
+                parent_id,
+                revision,
+            },
+            EntryKindTag::File => Entry::File {
+                file_id,
+                name,
+                parent_id,
+                revision,
+                text_sha1: record.text_sha1()?.map(|s| s.to_vec()),
+                text_size: record.text_size(),
+                text_id: None,
+                executable: record.executable(),
+            },
+            EntryKindTag::Symlink => {
+                let (off, len) = record.extra();
+                let symlink_target = if len == 0 {
+                    None
+                } else {
+                    Some(String::from_utf8_lossy(&get((off, len))?).into_owned())
+                };
+                Entry::Link {
+                    file_id,
+                    name,
+                    parent_id,
+                    symlink_target,
+                    revision,
+                }
+            }
+            EntryKindTag::TreeReference => {
+                let (off, len) = record.extra();
+                let reference_revision = if len == 0 {
+                    None
+                } else {
+                    Some(crate::RevisionId::from(get((off, len))?))
+                };
+                Entry::TreeReference {
+                    file_id,
+                    name,
+                    parent_id,
+                    revision,
+                    reference_revision,
+                }
+            }
+        })
+    }
+}
+
+/// Errors that can occur while serializing an inventory to the binary
+/// format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryBinaryWriteError {
+    /// A `text_sha1` is longer than the 20 bytes the fixed-size record
+    /// reserves for it (a SHA-1 digest is always exactly that long).
+    TextSha1TooLong(usize),
+}
+
+impl std::fmt::Display for InventoryBinaryWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryBinaryWriteError::TextSha1TooLong(len) => write!(
+                f,
+                "text_sha1 is {} bytes, more than the {} the binary format reserves",
+                len, TEXT_SHA1_CAPACITY
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InventoryBinaryWriteError {}
+
+fn push_string(strings: &mut Vec<u8>, bytes: &[u8]) -> (u32, u16) {
+    let offset = strings.len() as u32;
+    strings.extend_from_slice(bytes);
+    (offset, bytes.len() as u16)
+}
+
+/// Serialize `entries` (every entry in the inventory, including the root)
+/// into the binary format parsed by [`InventoryReader`].
+///
+/// Entries are written in file_id order so [`InventoryReader::get_entry`]
+/// can binary-search the resulting record array.
+pub fn write_inventory_binary(
+    root_file_id: &FileId,
+    entries: &[Entry],
+) -> Result<Vec<u8>, InventoryBinaryWriteError> {
+    let mut sorted: Vec<&Entry> = entries.iter().collect();
+    sorted.sort_by(|a, b| a.file_id().bytes().cmp(b.file_id().bytes()));
+
+    let mut strings = Vec::new();
+    let mut records = Vec::with_capacity(sorted.len() * RECORD_SIZE);
+
+    for entry in sorted {
+        let mut record = [0u8; RECORD_SIZE];
+        record[0] = match entry {
+            Entry::File { .. } => EntryKindTag::File as u8,
+            Entry::Directory { .. } => EntryKindTag::Directory as u8,
+            Entry::TreeReference { .. } => EntryKindTag::TreeReference as u8,
+            Entry::Link { .. } => EntryKindTag::Symlink as u8,
+        };
+
+        let (name_off, name_len) = push_string(&mut strings, entry.name().as_bytes());
+        record[1..5].copy_from_slice(&name_off.to_be_bytes());
+        record[5..7].copy_from_slice(&name_len.to_be_bytes());
+
+        let (fid_off, fid_len) = push_string(&mut strings, entry.file_id().bytes());
+        record[7..11].copy_from_slice(&fid_off.to_be_bytes());
+        record[11..13].copy_from_slice(&fid_len.to_be_bytes());
+
+        let (parent_off, parent_len) = match entry.parent_id() {
+            Some(parent_id) => push_string(&mut strings, parent_id.bytes()),
+            None => (0, 0),
+        };
+        record[13..17].copy_from_slice(&parent_off.to_be_bytes());
+        record[17..19].copy_from_slice(&parent_len.to_be_bytes());
+
+        let (rev_off, rev_len) = match entry.revision() {
+            Some(revision) => push_string(&mut strings, revision.bytes()),
+            None => (0, 0),
+        };
+        record[19..23].copy_from_slice(&rev_off.to_be_bytes());
+        record[23..25].copy_from_slice(&rev_len.to_be_bytes());
+
+        let (extra_off, extra_len) = match entry {
+            Entry::Link {
+                symlink_target: Some(target),
+                ..
+            } => push_string(&mut strings, target.as_bytes()),
+            Entry::TreeReference {
+                reference_revision: Some(reference_revision),
+                ..
+            } => push_string(&mut strings, reference_revision.bytes()),
+            _ => (0, 0),
+        };
+        record[25..29].copy_from_slice(&extra_off.to_be_bytes());
+        record[29..31].copy_from_slice(&extra_len.to_be_bytes());
+
+        if let Entry::File {
+            text_size,
+            executable,
+            text_sha1,
+            ..
+        } = entry
+        {
+            record[31..39].copy_from_slice(&text_size.unwrap_or(u64::MAX).to_be_bytes());
+            record[39] = *executable as u8;
+            if let Some(text_sha1) = text_sha1 {
+                if text_sha1.len() > TEXT_SHA1_CAPACITY {
+                    return Err(InventoryBinaryWriteError::TextSha1TooLong(text_sha1.len()));
+                }
+                record[40] = text_sha1.len() as u8;
+                record[41..41 + text_sha1.len()].copy_from_slice(text_sha1);
+            }
+        }
+
+        records.extend_from_slice(&record);
+    }
+
+    let mut data = records;
+    data.extend_from_slice(&strings);
+
+    let mut out = Vec::with_capacity(4 + 1 + 2 + root_file_id.bytes().len() + 4 + 4 + 8 + data.len());
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.extend_from_slice(&(root_file_id.bytes().len() as u16).to_be_bytes());
+    out.extend_from_slice(root_file_id.bytes());
+    out.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&content_checksum(&data).to_be_bytes());
+    out.extend_from_slice(&data);
+
+    Ok(out)
+}
+
+/// The owned storage backing an [`InventoryFile`]: either a memory map of
+/// the file (the fast path on local disk, no copy) or a buffer read in
+/// full (the safe fallback for network filesystems).
+enum InventoryBacking {
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl InventoryBacking {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            InventoryBacking::Mapped(mmap) => &mmap[..],
+            InventoryBacking::Owned(buf) => buf.as_slice(),
+        }
+    }
+}
+
+/// An opened binary inventory file, ready to be parsed into an
+/// [`InventoryReader`].
+///
+/// `mmap`-ing a file breaks badly over NFS and similar network filesystems
+/// (stale file handles, `SIGBUS` on truncation by another client), so
+/// opening auto-detects the filesystem the file lives on and falls back to
+/// a buffered `read` into an owned `Vec<u8>` there, while still exposing
+/// the same zero-copy entry-view API over the owned buffer. Callers in
+/// restricted environments (containers with unreliable `statfs`, sandboxes)
+/// can force one strategy or the other via `force_buffered`.
+pub struct InventoryFile {
+    backing: InventoryBacking,
+}
+
+impl InventoryFile {
+    /// Open the binary inventory file at `path`.
+    ///
+    /// `force_buffered` overrides the automatic filesystem detection:
+    /// `Some(true)` always reads the file into memory, `Some(false)`
+    /// always attempts to `mmap` it, and `None` picks automatically based
+    /// on whether `path` lives on a network filesystem.
+    pub fn open(path: &Path, force_buffered: Option<bool>) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let use_mmap = match force_buffered {
+            Some(force_buffered) => !force_buffered,
+            None => !is_network_filesystem(path)?,
+        };
+
+        let backing = if use_mmap {
+            // SAFETY: the file is not expected to be truncated or modified
+            // concurrently for the lifetime of the mapping; that invariant
+            // is why NFS and similar filesystems are routed to the
+            // buffered path above instead.
+            match unsafe { memmap2::Mmap::map(&file) } {
+                Ok(mmap) => InventoryBacking::Mapped(mmap),
+                Err(_) => InventoryBacking::Owned(std::fs::read(path)?),
+            }
+        } else {
+            InventoryBacking::Owned(std::fs::read(path)?)
+        };
+
+        Ok(InventoryFile { backing })
+    }
+
+    /// Parse the docket header and expose a lazy [`InventoryReader`] over
+    /// the backing bytes, whether mapped or owned.
+    pub fn reader(&self) -> Result<InventoryReader<'_>, InventoryBinaryError> {
+        InventoryReader::parse(self.backing.as_bytes())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_network_filesystem(path: &Path) -> io::Result<bool> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // Magic numbers for the network filesystem types that are known to
+    // misbehave under mmap; see statfs(2)/the corresponding kernel magic.h.
+    const NFS_SUPER_MAGIC: i64 = 0x6969;
+    const CIFS_MAGIC_NUMBER: i64 = 0xFF534D42u32 as i64;
+    const SMB_SUPER_MAGIC: i64 = 0x517B;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut statfs_buf: libc::statfs = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::statfs(c_path.as_ptr(), &mut statfs_buf) };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let f_type = statfs_buf.f_type as i64;
+    Ok(f_type == NFS_SUPER_MAGIC || f_type == CIFS_MAGIC_NUMBER || f_type == SMB_SUPER_MAGIC)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_filesystem(_path: &Path) -> io::Result<bool> {
+    // We don't have a portable way to query the filesystem type outside of
+    // Linux; be conservative and prefer the buffered path rather than risk
+    // mmap-ing an unexpectedly networked mount.
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RevisionId;
+
+    fn file_entry(file_id: &str, name: &str, parent_id: Option<&str>) -> Entry {
+        Entry::File {
+            file_id: FileId::from(file_id.as_bytes()),
+            name: name.to_string(),
+            parent_id: parent_id.map(|id| FileId::from(id.as_bytes())),
+            revision: Some(RevisionId::from("rev-1".as_bytes())),
+            text_sha1: Some(vec![0u8; 20]),
+            text_size: Some(0),
+            text_id: None,
+            executable: false,
+        }
+    }
+
+    fn dir_entry(file_id: &str, name: &str, parent_id: Option<&str>) -> Entry {
+        Entry::Directory {
+            file_id: FileId::from(file_id.as_bytes()),
+            name: name.to_string(),
+            parent_id: parent_id.map(|id| FileId::from(id.as_bytes())),
+            revision: Some(RevisionId::from("rev-1".as_bytes())),
+        }
+    }
+
+    fn link_entry(file_id: &str, name: &str, parent_id: Option<&str>, target: &str) -> Entry {
+        Entry::Link {
+            file_id: FileId::from(file_id.as_bytes()),
+            name: name.to_string(),
+            parent_id: parent_id.map(|id| FileId::from(id.as_bytes())),
+            symlink_target: Some(target.to_string()),
+            revision: Some(RevisionId::from("rev-1".as_bytes())),
+        }
+    }
+
+    #[test]
+    fn round_trips_every_entry_kind() {
+        let root_id = FileId::from("root-id".as_bytes());
+        let entries = vec![
+            dir_entry("root-id", "", None),
+            file_entry("file-id", "file.txt", Some("root-id")),
+            link_entry("link-id", "a-link", Some("root-id"), "target"),
+            Entry::TreeReference {
+                file_id: FileId::from("tree-ref-id".as_bytes()),
+                name: "subtree".to_string(),
+                parent_id: Some(FileId::from("root-id".as_bytes())),
+                revision: Some(RevisionId::from("rev-1".as_bytes())),
+                reference_revision: Some(RevisionId::from("sub-rev-1".as_bytes())),
+            },
+        ];
+
+        let bytes = write_inventory_binary(&root_id, &entries).unwrap();
+        let reader = InventoryReader::parse(&bytes).unwrap();
+
+        assert_eq!(reader.root_file_id, root_id);
+        assert_eq!(reader.len(), entries.len());
+
+        let mut decoded: Vec<Entry> = reader.iter_entries().collect::<Result<_, _>>().unwrap();
+        decoded.sort_by(|a, b| a.file_id().bytes().cmp(b.file_id().bytes()));
+        let mut expected = entries;
+        expected.sort_by(|a, b| a.file_id().bytes().cmp(b.file_id().bytes()));
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn get_entry_finds_entries_by_binary_search() {
+        let root_id = FileId::from("root-id".as_bytes());
+        let entries = vec![
+            dir_entry("root-id", "", None),
+            file_entry("aaa", "a.txt", Some("root-id")),
+            file_entry("zzz", "z.txt", Some("root-id")),
+        ];
+
+        let bytes = write_inventory_binary(&root_id, &entries).unwrap();
+        let reader = InventoryReader::parse(&bytes).unwrap();
+
+        assert_eq!(
+            reader.get_entry(&FileId::from("zzz".as_bytes())).unwrap(),
+            Some(file_entry("zzz", "z.txt", Some("root-id")))
+        );
+        assert_eq!(
+            reader
+                .get_entry(&FileId::from("missing".as_bytes()))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn write_rejects_oversized_text_sha1() {
+        let root_id = FileId::from("root-id".as_bytes());
+        let mut entry = file_entry("file-id", "file.txt", None);
+        if let Entry::File { text_sha1, .. } = &mut entry {
+            *text_sha1 = Some(vec![0u8; TEXT_SHA1_CAPACITY + 1]);
+        }
+
+        let err = write_inventory_binary(&root_id, &[entry]).unwrap_err();
+
+        assert!(matches!(err, InventoryBinaryWriteError::TextSha1TooLong(_)));
+    }
+
+    #[test]
+    fn parse_rejects_corrupted_data() {
+        let root_id = FileId::from("root-id".as_bytes());
+        let entries = vec![dir_entry("root-id", "", None)];
+        let mut bytes = write_inventory_binary(&root_id, &entries).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xFF;
+
+        let err = match InventoryReader::parse(&bytes) {
+            Err(err) => err,
+            Ok(_) => panic!("expected a checksum mismatch error"),
+        };
+
+        assert_eq!(err, InventoryBinaryError::ChecksumMismatch);
+    }
+
+    #[test]
+    fn build_entry_rejects_invalid_text_sha1_length() {
+        let root_id = FileId::from("root-id".as_bytes());
+        let entries = vec![file_entry("file-id", "file.txt", None)];
+        let mut bytes = write_inventory_binary(&root_id, &entries).unwrap();
+
+        // Corrupt the length byte of the lone record's inline text_sha1
+        // (byte offset 40 within the record) to a value the fixed-size
+        // record can't hold, then recompute the docket checksum so the
+        // corruption is only caught by the record-level bounds check, not
+        // the outer checksum.
+        let root_file_id_len = u16::from_be_bytes([bytes[5], bytes[6]]) as usize;
+        let data_start = 5 + 2 + root_file_id_len + 4 + 4 + 8;
+        bytes[data_start + 40] = 255;
+        let checksum_pos = data_start - 8;
+        let new_checksum = content_checksum(&bytes[data_start..]);
+        bytes[checksum_pos..checksum_pos + 8].copy_from_slice(&new_checksum.to_be_bytes());
+
+        let reader = InventoryReader::parse(&bytes).unwrap();
+        let err = reader
+            .get_entry(&FileId::from("file-id".as_bytes()))
+            .unwrap_err();
+
+        assert_eq!(err, InventoryBinaryError::InvalidTextSha1Length(255));
+    }
+}
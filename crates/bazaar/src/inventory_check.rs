@@ -0,0 +1,178 @@
+use crate::inventory::{Entry, Inventory};
+use crate::inventory_delta::InventoryDeltaInconsistency;
+use crate::{FileId, RevisionId};
+use unicode_normalization::is_nfc;
+
+/// The sha1 of an empty byte string, the content hash bzr assigns to
+/// directories and other entries that carry no text of their own.
+pub const EMPTY_TEXT_SHA1: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+
+/// A single `(revision_id, text_key, kind, sha1)` tuple that
+/// [`check_inventory`] collects for entries whose text needs to be verified
+/// against the repository's text store, mirroring what the Python
+/// `checker.add_pending_item` calls used to receive one at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingItem {
+    pub revision_id: RevisionId,
+    pub text_key: (FileId, Option<RevisionId>),
+    pub kind: &'static str,
+    pub sha1: Vec<u8>,
+}
+
+/// The result of a single-pass [`check_inventory`] walk.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CheckReport {
+    pub inconsistencies: Vec<InventoryDeltaInconsistency>,
+    pub pending_items: Vec<PendingItem>,
+}
+
+/// Walk every entry in `inventory` once, collecting inconsistencies and
+/// pending text-verification items instead of round-tripping into Python
+/// per entry the way `common_ie_check` used to.
+///
+/// `rich_roots` controls whether the root directory's (always empty)
+/// content is expected to be tracked as a pending item, matching the
+/// `rich_root` feature flag used elsewhere in the repository.
+pub fn check_inventory(revision_id: &RevisionId, inventory: &Inventory, rich_roots: bool) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    // `Inventory::iter()` walks a `HashMap`, so its order isn't stable
+    // across runs; sort by file_id first so `inconsistencies` and
+    // `pending_items` come out in a reproducible order regardless of hash
+    // iteration order.
+    let mut entries: Vec<&Entry> = inventory.iter().collect();
+    entries.sort_by(|a, b| a.file_id().bytes().cmp(b.file_id().bytes()));
+
+    for entry in entries {
+        check_parent(inventory, entry, &mut report.inconsistencies);
+        check_name(entry, &mut report.inconsistencies);
+
+        match entry {
+            Entry::File {
+                text_size,
+                text_sha1,
+                ..
+            } => {
+                if text_size.is_none() {
+                    report.inconsistencies.push(InventoryDeltaInconsistency::MissingTextSize(
+                        entry.name().to_string(),
+                        entry.file_id().clone(),
+                    ));
+                }
+                report.pending_items.push(PendingItem {
+                    revision_id: revision_id.clone(),
+                    text_key: (entry.file_id().clone(), entry.revision().cloned()),
+                    kind: "text",
+                    sha1: text_sha1.clone().unwrap_or_default(),
+                });
+            }
+            Entry::Link { symlink_target, .. } => {
+                if symlink_target.is_none() {
+                    report
+                        .inconsistencies
+                        .push(InventoryDeltaInconsistency::MissingSymlinkTarget(
+                            entry.name().to_string(),
+                            entry.file_id().clone(),
+                        ));
+                }
+            }
+            Entry::Directory { parent_id, .. } => {
+                if rich_roots || parent_id.is_some() {
+                    report.pending_items.push(PendingItem {
+                        revision_id: revision_id.clone(),
+                        text_key: (entry.file_id().clone(), entry.revision().cloned()),
+                        kind: "text",
+                        sha1: EMPTY_TEXT_SHA1.as_bytes().to_vec(),
+                    });
+                }
+            }
+            Entry::TreeReference { .. } => {}
+        }
+    }
+
+    report
+}
+
+fn check_parent(inventory: &Inventory, entry: &Entry, out: &mut Vec<InventoryDeltaInconsistency>) {
+    if let Some(parent_id) = entry.parent_id() {
+        if !inventory.has_id(parent_id) {
+            out.push(InventoryDeltaInconsistency::MissingParent(
+                entry.name().to_string(),
+                entry.file_id().clone(),
+            ));
+        }
+    }
+}
+
+fn check_name(entry: &Entry, out: &mut Vec<InventoryDeltaInconsistency>) {
+    // The root entry is conventionally named "" and is exempt from the
+    // usual single-path-component rules.
+    if entry.parent_id().is_none() {
+        return;
+    }
+    if !crate::inventory::is_valid_name(entry.name()) {
+        out.push(InventoryDeltaInconsistency::InvalidName(
+            entry.name().to_string(),
+            entry.file_id().clone(),
+        ));
+    } else if !is_nfc(entry.name()) {
+        out.push(InventoryDeltaInconsistency::NonNormalizedName(
+            entry.name().to_string(),
+            entry.file_id().clone(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn missing_parent_entry(file_id: &str, missing_parent: &str) -> Entry {
+        Entry::Directory {
+            file_id: FileId::from(file_id.as_bytes()),
+            name: file_id.to_string(),
+            parent_id: Some(FileId::from(missing_parent.as_bytes())),
+            revision: None,
+        }
+    }
+
+    #[test]
+    fn inconsistencies_are_ordered_by_file_id_regardless_of_hash_order() {
+        let root = Entry::Directory {
+            file_id: FileId::from("root-id".as_bytes()),
+            name: "".to_string(),
+            parent_id: None,
+            revision: None,
+        };
+        // Several entries with simultaneous inconsistencies (missing
+        // parents), inserted in an order that doesn't match file_id order,
+        // to catch the report coming out in `HashMap` iteration order.
+        let inventory = Inventory::from_iter([
+            root,
+            missing_parent_entry("zzz", "no-such-parent"),
+            missing_parent_entry("aaa", "no-such-parent"),
+            missing_parent_entry("mmm", "no-such-parent"),
+        ]);
+
+        let revision_id = RevisionId::from("rev-1".as_bytes());
+        let report = check_inventory(&revision_id, &inventory, false);
+
+        let file_ids: Vec<&FileId> = report
+            .inconsistencies
+            .iter()
+            .map(|inconsistency| match inconsistency {
+                InventoryDeltaInconsistency::MissingParent(_, fid) => fid,
+                other => panic!("unexpected inconsistency: {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(
+            file_ids,
+            vec![
+                &FileId::from("aaa".as_bytes()),
+                &FileId::from("mmm".as_bytes()),
+                &FileId::from("zzz".as_bytes()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1503 @@
+use crate::inventory::{Entry, Inventory};
+use crate::{FileId, RevisionId};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+/// A single add/remove/rename/modify record in an [`InventoryDelta`].
+///
+/// `old_path`/`new_path` follow the usual bzr convention: `None` for
+/// `old_path` means the file_id was just introduced, `None` for `new_path`
+/// means it was deleted, and both present (possibly equal) means it is
+/// retained, with `new_entry` carrying the up to date metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryDeltaEntry {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub file_id: FileId,
+    pub new_entry: Option<Entry>,
+}
+
+/// Ways an [`InventoryDelta`] can fail to describe a consistent change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryDeltaInconsistency {
+    /// An entry has neither an old nor a new path.
+    NoPath,
+    /// The same file_id appears in more than one entry.
+    DuplicateFileId(String, FileId),
+    /// The same old_path appears in more than one entry.
+    DuplicateOldPath(String, FileId),
+    /// The same new_path appears in more than one entry.
+    DuplicateNewPath(String, FileId),
+    /// `new_entry.file_id()` doesn't match the entry's own file_id.
+    MismatchedId(String, FileId, FileId),
+    /// `new_entry` is set but there is no `new_path` to place it at.
+    EntryWithoutPath(String, FileId),
+    /// `new_path` is set but there is no `new_entry` describing it.
+    PathWithoutEntry(String, FileId),
+    /// An entry's `parent_id` doesn't refer to any entry in the inventory
+    /// being checked.
+    MissingParent(String, FileId),
+    /// An entry's name is not a legal single path component.
+    InvalidName(String, FileId),
+    /// An entry's name is legal but not Unicode-NFC normalized.
+    NonNormalizedName(String, FileId),
+    /// A file entry has no recorded `text_size`.
+    MissingTextSize(String, FileId),
+    /// A symlink entry has no recorded target.
+    MissingSymlinkTarget(String, FileId),
+    /// An entry refers to a file_id that isn't present in the inventory
+    /// being modified (e.g. deleting a file_id that was never added).
+    UnknownId(String, FileId),
+    /// An entry's `new_path` is already occupied by a different, untouched
+    /// file_id already present in the inventory being modified.
+    PathCollision(String, FileId),
+}
+
+/// Errors raised while parsing the textual inventory-delta wire format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryDeltaParseError {
+    /// The input is structurally malformed.
+    Invalid(String),
+    /// The input is well-formed but uses a feature this parser doesn't
+    /// support (e.g. a newer format version).
+    Incompatible(String),
+    /// [`parse_inventory_delta_from_reader`] read a number of bytes that
+    /// disagrees with the declared payload length.
+    LengthMismatch { declared: u64, actual: u64 },
+    /// [`parse_inventory_delta_from_reader`]'s payload doesn't match its
+    /// declared content checksum.
+    ChecksumMismatch,
+}
+
+/// Errors raised while serializing an [`InventoryDelta`] to the wire
+/// format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryDeltaSerializeError {
+    Invalid(String),
+    UnsupportedKind(String),
+}
+
+/// An ordered sequence of per-file changes that transforms one inventory
+/// into another.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InventoryDelta(Vec<InventoryDeltaEntry>);
+
+impl From<Vec<InventoryDeltaEntry>> for InventoryDelta {
+    fn from(entries: Vec<InventoryDeltaEntry>) -> Self {
+        InventoryDelta(entries)
+    }
+}
+
+impl InventoryDelta {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&InventoryDeltaEntry> {
+        self.0.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, InventoryDeltaEntry> {
+        self.0.iter()
+    }
+
+    pub fn push(&mut self, entry: InventoryDeltaEntry) {
+        self.0.push(entry);
+    }
+
+    /// Sort entries by new_path (falling back to old_path for deletions),
+    /// the order the wire format and most consumers expect.
+    pub fn sort(&mut self) {
+        self.0.sort_by(|a, b| {
+            let a_key = a.new_path.as_deref().or(a.old_path.as_deref()).unwrap_or("");
+            let b_key = b.new_path.as_deref().or(b.old_path.as_deref()).unwrap_or("");
+            a_key.cmp(b_key)
+        });
+    }
+
+    /// Validate internal consistency: every entry has a path, no file_id or
+    /// path is duplicated, and every `new_path`/`new_entry` pair is
+    /// complete.
+    pub fn check(&self) -> Result<(), InventoryDeltaInconsistency> {
+        use std::collections::HashSet;
+
+        let mut seen_ids = HashSet::new();
+        let mut seen_old_paths = HashSet::new();
+        let mut seen_new_paths = HashSet::new();
+
+        for entry in &self.0 {
+            if entry.old_path.is_none() && entry.new_path.is_none() {
+                return Err(InventoryDeltaInconsistency::NoPath);
+            }
+
+            if !seen_ids.insert(entry.file_id.clone()) {
+                let path = entry
+                    .new_path
+                    .clone()
+                    .or_else(|| entry.old_path.clone())
+                    .unwrap_or_default();
+                return Err(InventoryDeltaInconsistency::DuplicateFileId(
+                    path,
+                    entry.file_id.clone(),
+                ));
+            }
+
+            if let Some(ref old_path) = entry.old_path {
+                if !seen_old_paths.insert(old_path.clone()) {
+                    return Err(InventoryDeltaInconsistency::DuplicateOldPath(
+                        old_path.clone(),
+                        entry.file_id.clone(),
+                    ));
+                }
+            }
+
+            match (&entry.new_path, &entry.new_entry) {
+                (Some(new_path), Some(new_entry)) => {
+                    if !seen_new_paths.insert(new_path.clone()) {
+                        return Err(InventoryDeltaInconsistency::DuplicateNewPath(
+                            new_path.clone(),
+                            entry.file_id.clone(),
+                        ));
+                    }
+                    if new_entry.file_id() != &entry.file_id {
+                        return Err(InventoryDeltaInconsistency::MismatchedId(
+                            new_path.clone(),
+                            entry.file_id.clone(),
+                            new_entry.file_id().clone(),
+                        ));
+                    }
+                }
+                (Some(new_path), None) => {
+                    return Err(InventoryDeltaInconsistency::PathWithoutEntry(
+                        new_path.clone(),
+                        entry.file_id.clone(),
+                    ));
+                }
+                (None, Some(_)) => {
+                    return Err(InventoryDeltaInconsistency::EntryWithoutPath(
+                        String::new(),
+                        entry.file_id.clone(),
+                    ));
+                }
+                (None, None) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compose `self` (mapping inventory A to B) with `other` (mapping B
+    /// to C) into a single delta mapping A to C, the inverse of splitting
+    /// a delta chain into per-revision steps.
+    pub fn compose(&self, other: &InventoryDelta) -> InventoryDelta {
+        use std::collections::HashMap;
+
+        let other_by_id: HashMap<&FileId, &InventoryDeltaEntry> =
+            other.0.iter().map(|e| (&e.file_id, e)).collect();
+
+        let mut file_ids: Vec<&FileId> = self.0.iter().map(|e| &e.file_id).collect();
+        file_ids.extend(other.0.iter().map(|e| &e.file_id));
+        let mut seen = std::collections::HashSet::new();
+        file_ids.retain(|id| seen.insert(*id));
+
+        let self_by_id: HashMap<&FileId, &InventoryDeltaEntry> =
+            self.0.iter().map(|e| (&e.file_id, e)).collect();
+
+        let mut composed = Vec::new();
+        for file_id in file_ids {
+            match (self_by_id.get(file_id), other_by_id.get(file_id)) {
+                (Some(a), None) => composed.push((*a).clone()),
+                (None, Some(b)) => composed.push((*b).clone()),
+                (Some(a), Some(b)) => {
+                    // Added in self, deleted in other: the two cancel out.
+                    if a.old_path.is_none() && b.new_path.is_none() {
+                        continue;
+                    }
+                    composed.push(InventoryDeltaEntry {
+                        old_path: a.old_path.clone(),
+                        new_path: b.new_path.clone(),
+                        file_id: file_id.clone(),
+                        new_entry: b.new_entry.clone(),
+                    });
+                }
+                (None, None) => unreachable!("file_id collected from one of the two deltas"),
+            }
+        }
+
+        InventoryDelta(composed)
+    }
+
+    /// Apply this delta to `inventory`, returning the resulting inventory.
+    ///
+    /// `inventory` is left untouched; the result is built on a clone of
+    /// it. Runs [`InventoryDelta::check`] first so malformed deltas (e.g.
+    /// a duplicated `new_path`) are rejected before any mutation happens.
+    pub fn apply(&self, inventory: &Inventory) -> Result<Inventory, InventoryDeltaInconsistency> {
+        self.check()?;
+        let mut result = inventory.clone();
+        apply_inventory_delta_streaming(&mut result, self.0.iter().cloned())?;
+        Ok(result)
+    }
+
+    /// Diff `old` against `new`, producing the delta that [`Self::apply`]
+    /// would turn `old` back into `new` with, the inverse of `apply`.
+    pub fn between(old: &Inventory, new: &Inventory) -> InventoryDelta {
+        let mut file_ids: Vec<&FileId> = old.iter().map(|e| e.file_id()).collect();
+        file_ids.extend(new.iter().map(|e| e.file_id()).filter(|id| !old.has_id(id)));
+
+        let mut entries = Vec::new();
+        for file_id in file_ids {
+            match (old.get(file_id), new.get(file_id)) {
+                (Some(_), None) => entries.push(InventoryDeltaEntry {
+                    old_path: path_in(old, file_id),
+                    new_path: None,
+                    file_id: file_id.clone(),
+                    new_entry: None,
+                }),
+                (None, Some(new_entry)) => entries.push(InventoryDeltaEntry {
+                    old_path: None,
+                    new_path: path_in(new, file_id),
+                    file_id: file_id.clone(),
+                    new_entry: Some(new_entry.clone()),
+                }),
+                (Some(old_entry), Some(new_entry)) => {
+                    let old_path = path_in(old, file_id);
+                    let new_path = path_in(new, file_id);
+                    if old_path != new_path
+                        || old_entry.parent_id() != new_entry.parent_id()
+                        || old_entry.kind() != new_entry.kind()
+                        || old_entry.revision() != new_entry.revision()
+                    {
+                        entries.push(InventoryDeltaEntry {
+                            old_path,
+                            new_path,
+                            file_id: file_id.clone(),
+                            new_entry: Some(new_entry.clone()),
+                        });
+                    }
+                }
+                (None, None) => unreachable!("file_id collected from one of the two inventories"),
+            }
+        }
+
+        InventoryDelta(entries)
+    }
+}
+
+/// Reconstruct the path of `file_id` within `inventory` by walking its
+/// parent_id chain up to the root, whose own path is the empty string.
+fn path_in(inventory: &Inventory, file_id: &FileId) -> Option<String> {
+    let mut components = Vec::new();
+    let mut current = inventory.get(file_id)?;
+    while let Some(parent_id) = current.parent_id() {
+        components.push(current.name().to_string());
+        current = inventory.get(parent_id)?;
+    }
+    components.reverse();
+    Some(components.join("/"))
+}
+
+/// Apply a single [`InventoryDeltaEntry`] to `inventory`, or report that it
+/// must wait for another entry in the same delta to land first.
+///
+/// Returns `Ok(true)` once the entry is fully applied. Returns `Ok(false)`
+/// when `allow_defer` is set and the entry is blocked on something that a
+/// later entry in the same delta might still resolve (its file_id is still
+/// present because the entry that deletes it hasn't arrived yet, its
+/// parent_id hasn't been inserted yet, or its new_path is still occupied by
+/// an id that hasn't been removed yet); the caller is expected to retry it.
+/// With `allow_defer` false, those same conditions are reported as the
+/// matching [`InventoryDeltaInconsistency`] instead, for entries that are
+/// still blocked once the whole delta has been seen.
+fn try_apply_entry(
+    inventory: &mut Inventory,
+    entry: &InventoryDeltaEntry,
+    inserted_ids: &mut HashSet<FileId>,
+    occupied_paths: &mut HashMap<String, FileId>,
+    allow_defer: bool,
+) -> Result<bool, InventoryDeltaInconsistency> {
+    if entry.old_path.is_some() {
+        if !inventory.has_id(&entry.file_id) {
+            return Err(InventoryDeltaInconsistency::UnknownId(
+                entry.old_path.clone().unwrap_or_default(),
+                entry.file_id.clone(),
+            ));
+        }
+        if entry.new_entry.is_none() {
+            inventory.remove(&entry.file_id);
+            occupied_paths.retain(|_, id| id != &entry.file_id);
+            return Ok(true);
+        }
+    }
+
+    let Some(new_entry) = &entry.new_entry else {
+        return Ok(true);
+    };
+
+    if entry.old_path.is_none() {
+        if inserted_ids.contains(&entry.file_id) {
+            return Err(InventoryDeltaInconsistency::DuplicateFileId(
+                entry.new_path.clone().unwrap_or_default(),
+                entry.file_id.clone(),
+            ));
+        }
+        if inventory.has_id(&entry.file_id) {
+            // Might still be vacated by this delta's own deletion entry for
+            // the same file_id, which just hasn't arrived yet.
+            if allow_defer {
+                return Ok(false);
+            }
+            return Err(InventoryDeltaInconsistency::DuplicateFileId(
+                entry.new_path.clone().unwrap_or_default(),
+                entry.file_id.clone(),
+            ));
+        }
+    }
+
+    if let Some(parent_id) = new_entry.parent_id() {
+        if !inventory.has_id(parent_id) && !inserted_ids.contains(parent_id) {
+            if allow_defer {
+                return Ok(false);
+            }
+            return Err(InventoryDeltaInconsistency::MissingParent(
+                entry.new_path.clone().unwrap_or_default(),
+                entry.file_id.clone(),
+            ));
+        }
+    }
+
+    if let Some(new_path) = &entry.new_path {
+        if let Some(existing_id) = occupied_paths.get(new_path) {
+            if existing_id != &entry.file_id {
+                if allow_defer {
+                    return Ok(false);
+                }
+                return Err(InventoryDeltaInconsistency::PathCollision(
+                    new_path.clone(),
+                    entry.file_id.clone(),
+                ));
+            }
+        }
+        occupied_paths.insert(new_path.clone(), entry.file_id.clone());
+    }
+
+    // A rename/reparent (as opposed to a brand new entry) vacates its old
+    // slot now that the move is committed, so another entry in the same
+    // delta is free to claim it.
+    if entry.old_path.as_deref() != entry.new_path.as_deref() {
+        if let Some(old_path) = &entry.old_path {
+            occupied_paths.remove(old_path);
+        }
+    }
+
+    inserted_ids.insert(entry.file_id.clone());
+    inventory.insert(new_entry.clone());
+    Ok(true)
+}
+
+/// Retry every entry in `pending` against the current state, repeatedly
+/// sweeping until a full pass makes no further progress, since resolving
+/// one entry (e.g. freeing up a path or introducing a parent) can be what
+/// unblocks another one still sitting in the queue.
+fn drain_pending(
+    inventory: &mut Inventory,
+    pending: &mut Vec<InventoryDeltaEntry>,
+    inserted_ids: &mut HashSet<FileId>,
+    occupied_paths: &mut HashMap<String, FileId>,
+) -> Result<(), InventoryDeltaInconsistency> {
+    loop {
+        let mut progressed = false;
+        let mut i = 0;
+        while i < pending.len() {
+            if try_apply_entry(inventory, &pending[i], inserted_ids, occupied_paths, true)? {
+                pending.remove(i);
+                progressed = true;
+            } else {
+                i += 1;
+            }
+        }
+        if !progressed {
+            return Ok(());
+        }
+    }
+}
+
+/// Apply a stream of [`InventoryDeltaEntry`] values to `inventory` in
+/// place, consuming `entries` exactly once, in order — a genuine one-shot
+/// `Iterator` works here, not just a re-iterable `Clone` source.
+///
+/// A delta's entries don't have to arrive in dependency order (e.g. moving
+/// a subtree deletes it at its old parent and re-inserts it under a new
+/// one, and either of those two entries might come first), so rather than
+/// re-reading the whole delta to sort it, an entry that isn't ready yet
+/// (its id is still occupied by something this same delta hasn't deleted
+/// yet, its parent_id hasn't been inserted yet, or its new_path is still
+/// taken by something this same delta hasn't vacated yet) is set aside in
+/// a small pending queue and retried whenever another entry's processing
+/// makes progress. Only entries still unresolved once the stream is
+/// exhausted are reported as errors, so the in-memory working set is
+/// bounded by how many entries are genuinely still waiting on something,
+/// not by the size of the delta.
+pub fn apply_inventory_delta_streaming<I>(
+    inventory: &mut Inventory,
+    entries: I,
+) -> Result<(), InventoryDeltaInconsistency>
+where
+    I: IntoIterator<Item = InventoryDeltaEntry>,
+{
+    // `Inventory` only indexes by file_id, so build a one-off path -> id
+    // index up front to catch a new_path that collides with an existing
+    // file_id, updating it as entries are applied below.
+    let mut occupied_paths: HashMap<String, FileId> = inventory
+        .iter()
+        .filter_map(|entry| Some((path_in(inventory, entry.file_id())?, entry.file_id().clone())))
+        .collect();
+    let mut inserted_ids: HashSet<FileId> = HashSet::new();
+    let mut pending: Vec<InventoryDeltaEntry> = Vec::new();
+
+    for entry in entries {
+        if !try_apply_entry(inventory, &entry, &mut inserted_ids, &mut occupied_paths, true)? {
+            pending.push(entry);
+            continue;
+        }
+        drain_pending(inventory, &mut pending, &mut inserted_ids, &mut occupied_paths)?;
+    }
+
+    drain_pending(inventory, &mut pending, &mut inserted_ids, &mut occupied_paths)?;
+
+    if let Some(entry) = pending.into_iter().next() {
+        try_apply_entry(inventory, &entry, &mut inserted_ids, &mut occupied_paths, false)?;
+    }
+
+    Ok(())
+}
+
+const NUL: u8 = 0;
+
+fn split_nul(line: &[u8]) -> Vec<&[u8]> {
+    line.split(|&b| b == NUL).collect()
+}
+
+fn path_to_bytes(path: Option<&str>) -> Vec<u8> {
+    match path {
+        Some(p) => p.as_bytes().to_vec(),
+        None => b"None".to_vec(),
+    }
+}
+
+fn bytes_to_path(bytes: &[u8]) -> Option<String> {
+    if bytes == b"None" {
+        None
+    } else {
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Serialize just the kind-specific content of an entry, the portion that
+/// follows the path/file_id/parent_id/revision fields on an inventory-delta
+/// line.
+pub fn serialize_inventory_entry(entry: &Entry) -> Result<Vec<u8>, InventoryDeltaSerializeError> {
+    let mut out = Vec::new();
+    match entry {
+        Entry::Directory { .. } => out.extend_from_slice(b"dir"),
+        Entry::File {
+            text_size,
+            executable,
+            text_sha1,
+            ..
+        } => {
+            out.extend_from_slice(b"file");
+            out.push(NUL);
+            out.extend_from_slice(text_size.unwrap_or(0).to_string().as_bytes());
+            out.push(NUL);
+            out.extend_from_slice(if *executable { b"Y" } else { b"N" });
+            out.push(NUL);
+            out.extend_from_slice(text_sha1.as_deref().unwrap_or(b""));
+        }
+        Entry::Link { symlink_target, .. } => {
+            out.extend_from_slice(b"symlink");
+            out.push(NUL);
+            out.extend_from_slice(symlink_target.as_deref().unwrap_or("").as_bytes());
+        }
+        Entry::TreeReference {
+            reference_revision, ..
+        } => {
+            out.extend_from_slice(b"tree");
+            out.push(NUL);
+            match reference_revision {
+                Some(r) => out.extend_from_slice(r.bytes()),
+                None => out.extend_from_slice(b"null:"),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Parse the kind-specific content of an inventory-delta line (the inverse
+/// of [`serialize_inventory_entry`]) into a full [`Entry`].
+pub fn parse_inventory_entry(
+    file_id: FileId,
+    name: String,
+    parent_id: Option<FileId>,
+    revision: Option<RevisionId>,
+    content: &[u8],
+) -> Entry {
+    let fields = split_nul(content);
+    match fields.first().copied().unwrap_or(b"") {
+        b"dir" => Entry::Directory {
+            file_id,
+            name,
+            parent_id,
+            revision,
+        },
+        b"file" => {
+            let text_size = fields
+                .get(1)
+                .and_then(|f| std::str::from_utf8(f).ok())
+                .and_then(|s| s.parse::<u64>().ok());
+            let executable = fields.get(2).map(|f| f.as_ref() == b"Y").unwrap_or(false);
+            let text_sha1 = fields
+                .get(3)
+                .filter(|f| !f.is_empty())
+                .map(|f| f.to_vec());
+            Entry::File {
+                file_id,
+                name,
+                parent_id,
+                revision,
+                text_sha1,
+                text_size,
+                text_id: None,
+                executable,
+            }
+        }
+        b"symlink" => {
+            let target = fields
+                .get(1)
+                .map(|f| String::from_utf8_lossy(f).into_owned())
+                .filter(|s| !s.is_empty());
+            Entry::Link {
+                file_id,
+                name,
+                parent_id,
+                symlink_target: target,
+                revision,
+            }
+        }
+        b"tree" => {
+            let reference_revision = fields
+                .get(1)
+                .filter(|f| f.as_ref() != b"null:")
+                .map(|f| RevisionId::from(f.to_vec()));
+            Entry::TreeReference {
+                file_id,
+                name,
+                parent_id,
+                revision,
+                reference_revision,
+            }
+        }
+        _ => Entry::Directory {
+            file_id,
+            name,
+            parent_id,
+            revision,
+        },
+    }
+}
+
+/// Serialize an [`InventoryDelta`] to the newline-delimited text wire
+/// format used when transmitting it over the smart protocol or storing it
+/// on disk.
+pub fn serialize_inventory_delta(
+    old_name: &RevisionId,
+    new_name: &RevisionId,
+    delta: &InventoryDelta,
+    versioned_root: bool,
+    tree_references: bool,
+) -> Result<Vec<Vec<u8>>, InventoryDeltaSerializeError> {
+    let mut lines = Vec::with_capacity(delta.len() + 5);
+    lines.push(b"format: bzr inventory delta v1 (bzr 1.14)\n".to_vec());
+    lines.push([b"parent: ".as_slice(), old_name.bytes(), b"\n"].concat());
+    lines.push([b"version: ".as_slice(), new_name.bytes(), b"\n"].concat());
+    lines.push(
+        format!("versioned_root: {}\n", versioned_root)
+            .into_bytes(),
+    );
+    lines.push(format!("tree_references: {}\n", tree_references).into_bytes());
+
+    for entry in delta.iter() {
+        let mut line = Vec::new();
+        line.extend_from_slice(&path_to_bytes(entry.old_path.as_deref()));
+        line.push(NUL);
+        line.extend_from_slice(&path_to_bytes(entry.new_path.as_deref()));
+        line.push(NUL);
+        line.extend_from_slice(entry.file_id.bytes());
+        line.push(NUL);
+        match entry.new_entry.as_ref().and_then(|e| e.parent_id()) {
+            Some(parent_id) => line.extend_from_slice(parent_id.bytes()),
+            None => line.extend_from_slice(b"None"),
+        }
+        line.push(NUL);
+        match entry.new_entry.as_ref().and_then(|e| e.revision()) {
+            Some(revision) => line.extend_from_slice(revision.bytes()),
+            None => line.extend_from_slice(b"null:"),
+        }
+        line.push(NUL);
+        match &entry.new_entry {
+            Some(new_entry) => line.extend_from_slice(&serialize_inventory_entry(new_entry)?),
+            None => line.extend_from_slice(b"deleted"),
+        }
+        line.push(b'\n');
+        lines.push(line);
+    }
+
+    Ok(lines)
+}
+
+/// Parse the newline-delimited text wire format produced by
+/// [`serialize_inventory_delta`].
+///
+/// Returns the parent and version revision ids, the `versioned_root` and
+/// `tree_references` flags, and the parsed delta.
+pub fn parse_inventory_delta(
+    lines: &[&[u8]],
+    allow_versioned_root: Option<bool>,
+    allow_tree_references: Option<bool>,
+) -> Result<(RevisionId, RevisionId, bool, bool, InventoryDelta), InventoryDeltaParseError> {
+    let mut lines = lines.iter();
+
+    let format_line = lines
+        .next()
+        .ok_or_else(|| InventoryDeltaParseError::Invalid("empty inventory delta".to_string()))?;
+    if !format_line.starts_with(b"format: bzr inventory delta v1") {
+        return Err(InventoryDeltaParseError::Incompatible(
+            "unknown inventory delta format".to_string(),
+        ));
+    }
+
+    fn header_value<'a>(line: &'a [u8], prefix: &str) -> Result<&'a [u8], InventoryDeltaParseError> {
+        line.strip_prefix(prefix.as_bytes())
+            .map(|v| v.strip_suffix(b"\n").unwrap_or(v))
+            .ok_or_else(|| InventoryDeltaParseError::Invalid(format!("expected {}", prefix)))
+    }
+
+    let parent = RevisionId::from(
+        header_value(
+            lines
+                .next()
+                .ok_or_else(|| InventoryDeltaParseError::Invalid("missing parent line".to_string()))?,
+            "parent: ",
+        )?
+        .to_vec(),
+    );
+    let version = RevisionId::from(
+        header_value(
+            lines
+                .next()
+                .ok_or_else(|| InventoryDeltaParseError::Invalid("missing version line".to_string()))?,
+            "version: ",
+        )?
+        .to_vec(),
+    );
+    let versioned_root = header_value(
+        lines.next().ok_or_else(|| {
+            InventoryDeltaParseError::Invalid("missing versioned_root line".to_string())
+        })?,
+        "versioned_root: ",
+    )? == b"true";
+    let tree_references = header_value(
+        lines.next().ok_or_else(|| {
+            InventoryDeltaParseError::Invalid("missing tree_references line".to_string())
+        })?,
+        "tree_references: ",
+    )? == b"true";
+
+    if let Some(allow) = allow_versioned_root {
+        if versioned_root && !allow {
+            return Err(InventoryDeltaParseError::Incompatible(
+                "versioned_root not supported".to_string(),
+            ));
+        }
+    }
+    if let Some(allow) = allow_tree_references {
+        if tree_references && !allow {
+            return Err(InventoryDeltaParseError::Incompatible(
+                "tree_references not supported".to_string(),
+            ));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for line in lines {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let fields = split_nul(line);
+        if fields.len() < 6 {
+            return Err(InventoryDeltaParseError::Invalid(
+                "malformed inventory delta line".to_string(),
+            ));
+        }
+        let old_path = bytes_to_path(fields[0]);
+        let new_path = bytes_to_path(fields[1]);
+        let file_id = FileId::from(fields[2].to_vec());
+        let parent_id = if fields[3] == b"None" {
+            None
+        } else {
+            Some(FileId::from(fields[3].to_vec()))
+        };
+        let revision = if fields[4] == b"null:" {
+            None
+        } else {
+            Some(RevisionId::from(fields[4].to_vec()))
+        };
+        let content = fields[5..].join(&NUL);
+
+        let new_entry = if content == b"deleted" {
+            None
+        } else {
+            let name = new_path
+                .as_deref()
+                .and_then(|p| p.rsplit('/').next())
+                .unwrap_or("")
+                .to_string();
+            Some(parse_inventory_entry(
+                file_id.clone(),
+                name,
+                parent_id,
+                revision,
+                &content,
+            ))
+        };
+
+        entries.push(InventoryDeltaEntry {
+            old_path,
+            new_path,
+            file_id,
+            new_entry,
+        });
+    }
+
+    Ok((
+        parent,
+        version,
+        versioned_root,
+        tree_references,
+        InventoryDelta::from(entries),
+    ))
+}
+
+/// Read exactly `declared_len` bytes of inventory-delta payload from
+/// `reader`, verify them against `expected_checksum` (an FNV-1a content
+/// checksum, the same non-cryptographic hash [`crate::inventory_binary`]
+/// uses), and only then hand them to [`parse_inventory_delta`].
+///
+/// Unlike that entrypoint, which trusts its caller to have already read a
+/// complete, uncorrupted set of lines, this is meant for delta payloads
+/// arriving over the smart protocol: a truncated or corrupted stream
+/// fails loudly here instead of silently producing a short but
+/// structurally "valid" delta.
+pub fn parse_inventory_delta_from_reader<R: Read>(
+    reader: &mut R,
+    declared_len: u64,
+    expected_checksum: u64,
+    allow_versioned_root: Option<bool>,
+    allow_tree_references: Option<bool>,
+) -> Result<(RevisionId, RevisionId, bool, bool, InventoryDelta), InventoryDeltaParseError> {
+    let mut payload = Vec::new();
+    reader
+        .take(declared_len)
+        .read_to_end(&mut payload)
+        .map_err(|e| InventoryDeltaParseError::Invalid(e.to_string()))?;
+
+    if payload.len() as u64 != declared_len {
+        return Err(InventoryDeltaParseError::LengthMismatch {
+            declared: declared_len,
+            actual: payload.len() as u64,
+        });
+    }
+
+    if crate::inventory_binary::content_checksum(&payload) != expected_checksum {
+        return Err(InventoryDeltaParseError::ChecksumMismatch);
+    }
+
+    let lines: Vec<&[u8]> = payload
+        .split_inclusive(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    parse_inventory_delta(&lines, allow_versioned_root, allow_tree_references)
+}
+
+const BINARY_MAGIC: &[u8; 4] = b"BID1";
+const BINARY_VERSION: u8 = 1;
+const FLAG_VERSIONED_ROOT: u8 = 0b01;
+const FLAG_TREE_REFERENCES: u8 = 0b10;
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Append a length-prefixed field: every field announces its own byte
+/// length, so a decoder can skip fields (or whole trailing records) it
+/// doesn't understand instead of having to parse every byte.
+fn write_lp(out: &mut Vec<u8>, data: &[u8]) {
+    write_u32(out, data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+fn write_opt(out: &mut Vec<u8>, data: Option<&[u8]>) {
+    match data {
+        Some(bytes) => {
+            out.push(1);
+            write_lp(out, bytes);
+        }
+        None => out.push(0),
+    }
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], InventoryDeltaParseError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| InventoryDeltaParseError::Invalid("truncated binary inventory delta".to_string()))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn take_u32(&mut self) -> Result<u32, InventoryDeltaParseError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn take_u8(&mut self) -> Result<u8, InventoryDeltaParseError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take_lp(&mut self) -> Result<&'a [u8], InventoryDeltaParseError> {
+        let len = self.take_u32()? as usize;
+        self.take(len)
+    }
+
+    fn take_opt(&mut self) -> Result<Option<&'a [u8]>, InventoryDeltaParseError> {
+        if self.take_u8()? == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(self.take_lp()?))
+        }
+    }
+}
+
+fn kind_tag(entry: &Entry) -> u8 {
+    match entry {
+        Entry::Directory { .. } => 0,
+        Entry::File { .. } => 1,
+        Entry::Link { .. } => 2,
+        Entry::TreeReference { .. } => 3,
+    }
+}
+
+/// Binary-safe counterpart to [`serialize_inventory_entry`]/
+/// [`parse_inventory_entry`]: every kind-specific field is length-prefixed
+/// instead of NUL-delimited, so a `text_sha1` or `symlink_target`
+/// containing an embedded NUL byte round-trips correctly. Used only by the
+/// binary delta codec; the text wire format keeps using the NUL-delimited
+/// helpers above for compatibility with existing tools that read it.
+fn write_entry_payload_binary(record: &mut Vec<u8>, entry: &Entry) {
+    match entry {
+        Entry::Directory { .. } => {}
+        Entry::File {
+            text_size,
+            executable,
+            text_sha1,
+            ..
+        } => {
+            write_opt(record, text_size.map(|s| s.to_le_bytes()).as_ref().map(|b| b.as_slice()));
+            record.push(if *executable { 1 } else { 0 });
+            write_opt(record, text_sha1.as_deref());
+        }
+        Entry::Link { symlink_target, .. } => {
+            write_opt(record, symlink_target.as_deref().map(str::as_bytes));
+        }
+        Entry::TreeReference {
+            reference_revision, ..
+        } => {
+            write_opt(record, reference_revision.as_ref().map(RevisionId::bytes));
+        }
+    }
+}
+
+/// Inverse of [`write_entry_payload_binary`].
+fn parse_entry_payload_binary(
+    kind: u8,
+    file_id: FileId,
+    name: String,
+    parent_id: Option<FileId>,
+    revision: Option<RevisionId>,
+    payload: &[u8],
+) -> Result<Entry, InventoryDeltaParseError> {
+    let mut cursor = Cursor::new(payload);
+    Ok(match kind {
+        0 => Entry::Directory {
+            file_id,
+            name,
+            parent_id,
+            revision,
+        },
+        1 => {
+            let text_size = cursor
+                .take_opt()?
+                .map(|b| {
+                    let bytes: [u8; 8] = b.try_into().map_err(|_| {
+                        InventoryDeltaParseError::Invalid("malformed text_size".to_string())
+                    })?;
+                    Ok::<_, InventoryDeltaParseError>(u64::from_le_bytes(bytes))
+                })
+                .transpose()?;
+            let executable = cursor.take_u8()? != 0;
+            let text_sha1 = cursor.take_opt()?.map(|b| b.to_vec());
+            Entry::File {
+                file_id,
+                name,
+                parent_id,
+                revision,
+                text_sha1,
+                text_size,
+                text_id: None,
+                executable,
+            }
+        }
+        2 => {
+            let symlink_target = cursor
+                .take_opt()?
+                .map(|b| String::from_utf8_lossy(b).into_owned());
+            Entry::Link {
+                file_id,
+                name,
+                parent_id,
+                symlink_target,
+                revision,
+            }
+        }
+        3 => {
+            let reference_revision = cursor.take_opt()?.map(|b| RevisionId::from(b.to_vec()));
+            Entry::TreeReference {
+                file_id,
+                name,
+                parent_id,
+                revision,
+                reference_revision,
+            }
+        }
+        other => {
+            return Err(InventoryDeltaParseError::Incompatible(format!(
+                "unknown binary inventory delta entry kind {}",
+                other
+            )))
+        }
+    })
+}
+
+/// Serialize an [`InventoryDelta`] to a compact, self-describing binary
+/// format: a short magic + version header, then one length-prefixed
+/// record per entry. Every field (and every record) announces its own
+/// byte length, so a decoder can validate structure and skip fields or
+/// trailing records it doesn't recognise, the same forward-compatibility
+/// trick Preserves-style length-delimited encodings use.
+pub fn serialize_inventory_delta_binary(
+    old_name: &RevisionId,
+    new_name: &RevisionId,
+    delta: &InventoryDelta,
+    versioned_root: bool,
+    tree_references: bool,
+) -> Result<Vec<u8>, InventoryDeltaSerializeError> {
+    let mut out = Vec::new();
+    out.extend_from_slice(BINARY_MAGIC);
+    out.push(BINARY_VERSION);
+    write_lp(&mut out, old_name.bytes());
+    write_lp(&mut out, new_name.bytes());
+    let mut flags = 0u8;
+    if versioned_root {
+        flags |= FLAG_VERSIONED_ROOT;
+    }
+    if tree_references {
+        flags |= FLAG_TREE_REFERENCES;
+    }
+    out.push(flags);
+    write_u32(&mut out, delta.len() as u32);
+
+    for entry in delta.iter() {
+        let mut record = Vec::new();
+        write_lp(&mut record, entry.file_id.bytes());
+        write_opt(&mut record, entry.old_path.as_deref().map(str::as_bytes));
+        write_opt(&mut record, entry.new_path.as_deref().map(str::as_bytes));
+        match &entry.new_entry {
+            Some(new_entry) => {
+                record.push(1);
+                write_opt(&mut record, new_entry.parent_id().map(FileId::bytes));
+                write_opt(&mut record, new_entry.revision().map(RevisionId::bytes));
+                record.push(kind_tag(new_entry));
+                let mut payload = Vec::new();
+                write_entry_payload_binary(&mut payload, new_entry);
+                write_lp(&mut record, &payload);
+            }
+            None => record.push(0),
+        }
+        write_lp(&mut out, &record);
+    }
+
+    Ok(out)
+}
+
+/// Parse the binary wire format produced by
+/// [`serialize_inventory_delta_binary`].
+pub fn parse_inventory_delta_binary(
+    data: &[u8],
+    allow_versioned_root: Option<bool>,
+    allow_tree_references: Option<bool>,
+) -> Result<(RevisionId, RevisionId, bool, bool, InventoryDelta), InventoryDeltaParseError> {
+    let mut cursor = Cursor::new(data);
+
+    if cursor.take(4)? != BINARY_MAGIC.as_slice() {
+        return Err(InventoryDeltaParseError::Invalid(
+            "not a binary inventory delta".to_string(),
+        ));
+    }
+    let version = cursor.take_u8()?;
+    if version != BINARY_VERSION {
+        return Err(InventoryDeltaParseError::Incompatible(format!(
+            "unsupported binary inventory delta version {}",
+            version
+        )));
+    }
+
+    let parent = RevisionId::from(cursor.take_lp()?.to_vec());
+    let target = RevisionId::from(cursor.take_lp()?.to_vec());
+    let flags = cursor.take_u8()?;
+    let versioned_root = flags & FLAG_VERSIONED_ROOT != 0;
+    let tree_references = flags & FLAG_TREE_REFERENCES != 0;
+
+    if let Some(allow) = allow_versioned_root {
+        if versioned_root && !allow {
+            return Err(InventoryDeltaParseError::Incompatible(
+                "versioned_root not supported".to_string(),
+            ));
+        }
+    }
+    if let Some(allow) = allow_tree_references {
+        if tree_references && !allow {
+            return Err(InventoryDeltaParseError::Incompatible(
+                "tree_references not supported".to_string(),
+            ));
+        }
+    }
+
+    let entry_count = cursor.take_u32()?;
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    for _ in 0..entry_count {
+        let record = cursor.take_lp()?;
+        let mut record = Cursor::new(record);
+
+        let file_id = FileId::from(record.take_lp()?.to_vec());
+        let old_path = record
+            .take_opt()?
+            .map(|b| String::from_utf8_lossy(b).into_owned());
+        let new_path = record
+            .take_opt()?
+            .map(|b| String::from_utf8_lossy(b).into_owned());
+
+        let new_entry = if record.take_u8()? == 0 {
+            None
+        } else {
+            let parent_id = record.take_opt()?.map(|b| FileId::from(b.to_vec()));
+            let revision = record.take_opt()?.map(|b| RevisionId::from(b.to_vec()));
+            let kind = record.take_u8()?;
+            let payload = record.take_lp()?;
+            let name = new_path
+                .as_deref()
+                .and_then(|p| p.rsplit('/').next())
+                .unwrap_or("")
+                .to_string();
+            Some(parse_entry_payload_binary(
+                kind,
+                file_id.clone(),
+                name,
+                parent_id,
+                revision,
+                payload,
+            )?)
+        };
+
+        entries.push(InventoryDeltaEntry {
+            old_path,
+            new_path,
+            file_id,
+            new_entry,
+        });
+    }
+
+    Ok((
+        parent,
+        target,
+        versioned_root,
+        tree_references,
+        InventoryDelta::from(entries),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Entry;
+
+    fn dir_entry(file_id: &str, name: &str, parent_id: Option<&str>) -> Entry {
+        Entry::Directory {
+            file_id: FileId::from(file_id.as_bytes()),
+            name: name.to_string(),
+            parent_id: parent_id.map(|p| FileId::from(p.as_bytes())),
+            revision: None,
+        }
+    }
+
+    fn add_entry(
+        delta: &mut InventoryDelta,
+        file_id: &str,
+        old_path: Option<&str>,
+        new_path: Option<&str>,
+        entry: Option<Entry>,
+    ) {
+        delta.push(InventoryDeltaEntry {
+            old_path: old_path.map(str::to_string),
+            new_path: new_path.map(str::to_string),
+            file_id: FileId::from(file_id.as_bytes()),
+            new_entry: entry,
+        });
+    }
+
+    #[test]
+    fn compose_carries_over_unrelated_entries() {
+        let mut a_to_b = InventoryDelta::default();
+        add_entry(
+            &mut a_to_b,
+            "file-1",
+            None,
+            Some("file-1"),
+            Some(dir_entry("file-1", "file-1", None)),
+        );
+
+        let mut b_to_c = InventoryDelta::default();
+        add_entry(
+            &mut b_to_c,
+            "file-2",
+            None,
+            Some("file-2"),
+            Some(dir_entry("file-2", "file-2", None)),
+        );
+
+        let composed = a_to_b.compose(&b_to_c);
+
+        assert_eq!(composed.len(), 2);
+        let ids: HashSet<FileId> = composed.iter().map(|e| e.file_id.clone()).collect();
+        assert!(ids.contains(&FileId::from("file-1".as_bytes())));
+        assert!(ids.contains(&FileId::from("file-2".as_bytes())));
+    }
+
+    #[test]
+    fn compose_merges_add_then_modify_into_a_single_add() {
+        let mut a_to_b = InventoryDelta::default();
+        add_entry(
+            &mut a_to_b,
+            "file-1",
+            None,
+            Some("file-1"),
+            Some(dir_entry("file-1", "file-1", None)),
+        );
+
+        let mut b_to_c = InventoryDelta::default();
+        add_entry(
+            &mut b_to_c,
+            "file-1",
+            Some("file-1"),
+            Some("renamed"),
+            Some(dir_entry("file-1", "renamed", None)),
+        );
+
+        let composed = a_to_b.compose(&b_to_c);
+
+        assert_eq!(composed.len(), 1);
+        let entry = composed.get(0).unwrap();
+        assert_eq!(entry.old_path, None);
+        assert_eq!(entry.new_path.as_deref(), Some("renamed"));
+    }
+
+    #[test]
+    fn compose_cancels_out_an_add_followed_by_a_delete() {
+        let mut a_to_b = InventoryDelta::default();
+        add_entry(
+            &mut a_to_b,
+            "file-1",
+            None,
+            Some("file-1"),
+            Some(dir_entry("file-1", "file-1", None)),
+        );
+
+        let mut b_to_c = InventoryDelta::default();
+        add_entry(&mut b_to_c, "file-1", Some("file-1"), None, None);
+
+        let composed = a_to_b.compose(&b_to_c);
+
+        assert!(composed.is_empty());
+    }
+
+    #[test]
+    fn apply_inserts_new_entries_under_an_existing_parent() {
+        let root = dir_entry("root-id", "", None);
+        let inventory = Inventory::from_iter([root]);
+
+        let mut delta = InventoryDelta::default();
+        add_entry(
+            &mut delta,
+            "file-1",
+            None,
+            Some("file-1"),
+            Some(dir_entry("file-1", "file-1", Some("root-id"))),
+        );
+
+        let result = delta.apply(&inventory).unwrap();
+
+        assert!(result.has_id(&FileId::from("file-1".as_bytes())));
+    }
+
+    #[test]
+    fn apply_rejects_new_path_colliding_with_an_untouched_file_id() {
+        let root = dir_entry("root-id", "", None);
+        let existing = dir_entry("existing-id", "taken", Some("root-id"));
+        let mut inventory = Inventory::from_iter([root, existing]);
+
+        let delta = vec![InventoryDeltaEntry {
+            old_path: None,
+            new_path: Some("taken".to_string()),
+            file_id: FileId::from("new-id".as_bytes()),
+            new_entry: Some(dir_entry("new-id", "taken", Some("root-id"))),
+        }];
+
+        let err = apply_inventory_delta_streaming(&mut inventory, delta).unwrap_err();
+
+        assert!(matches!(err, InventoryDeltaInconsistency::PathCollision(_, _)));
+    }
+
+    #[test]
+    fn apply_streaming_moves_a_subtree_from_a_borrowed_cloned_iterator() {
+        let root = dir_entry("root-id", "", None);
+        let old_parent = dir_entry("old-parent", "old", Some("root-id"));
+        let new_parent = dir_entry("new-parent", "new", Some("root-id"));
+        let child = dir_entry("child-id", "child", Some("old-parent"));
+        let mut inventory = Inventory::from_iter([root, old_parent, new_parent, child]);
+
+        let entries = [InventoryDeltaEntry {
+            old_path: Some("old/child".to_string()),
+            new_path: Some("new/child".to_string()),
+            file_id: FileId::from("child-id".as_bytes()),
+            new_entry: Some(dir_entry("child-id", "child", Some("new-parent"))),
+        }];
+
+        // Apply straight from a borrowed slice's cloned iterator, the same
+        // shape `InventoryDelta::apply` feeds in, rather than an owned
+        // `Vec` the function collects up front.
+        apply_inventory_delta_streaming(&mut inventory, entries.iter().cloned()).unwrap();
+
+        let moved = inventory
+            .get(&FileId::from("child-id".as_bytes()))
+            .unwrap();
+        assert_eq!(
+            moved.parent_id(),
+            Some(&FileId::from("new-parent".as_bytes()))
+        );
+    }
+
+    #[test]
+    fn apply_lets_a_new_entry_reuse_a_path_vacated_by_a_rename_in_the_same_delta() {
+        let root = dir_entry("root-id", "", None);
+        let renamed = dir_entry("renamed-id", "old-name", Some("root-id"));
+        let mut inventory = Inventory::from_iter([root, renamed]);
+
+        // "old-name" is freed up by the rename and immediately reused for a
+        // brand new, unrelated file_id within the same delta.
+        let delta = vec![
+            InventoryDeltaEntry {
+                old_path: Some("old-name".to_string()),
+                new_path: Some("new-name".to_string()),
+                file_id: FileId::from("renamed-id".as_bytes()),
+                new_entry: Some(dir_entry("renamed-id", "new-name", Some("root-id"))),
+            },
+            InventoryDeltaEntry {
+                old_path: None,
+                new_path: Some("old-name".to_string()),
+                file_id: FileId::from("new-id".as_bytes()),
+                new_entry: Some(dir_entry("new-id", "old-name", Some("root-id"))),
+            },
+        ];
+
+        apply_inventory_delta_streaming(&mut inventory, delta).unwrap();
+
+        assert!(inventory.has_id(&FileId::from("renamed-id".as_bytes())));
+        assert!(inventory.has_id(&FileId::from("new-id".as_bytes())));
+    }
+
+    /// Wraps an iterator without deriving `Clone`, so using it proves
+    /// `apply_inventory_delta_streaming` really does consume its input in a
+    /// single pass rather than merely accepting one that happens to be
+    /// `Clone`.
+    struct OneShot<I>(I);
+
+    impl<I: Iterator> Iterator for OneShot<I> {
+        type Item = I::Item;
+        fn next(&mut self) -> Option<Self::Item> {
+            self.0.next()
+        }
+    }
+
+    #[test]
+    fn apply_streaming_resolves_a_reparent_that_arrives_before_its_new_parent() {
+        let root = dir_entry("root-id", "", None);
+        let old_parent = dir_entry("old-parent", "old-parent", Some("root-id"));
+        let child = dir_entry("child-id", "child", Some("old-parent"));
+        let mut inventory = Inventory::from_iter([root, old_parent, child]);
+
+        // The child's move under "new-parent" is listed before the entry
+        // that actually creates "new-parent", so a single forward pass
+        // can't resolve it on first sight; it must be deferred and retried
+        // once the second entry lands.
+        let entries = vec![
+            InventoryDeltaEntry {
+                old_path: Some("old-parent/child".to_string()),
+                new_path: Some("new-parent/child".to_string()),
+                file_id: FileId::from("child-id".as_bytes()),
+                new_entry: Some(dir_entry("child-id", "child", Some("new-parent-id"))),
+            },
+            InventoryDeltaEntry {
+                old_path: None,
+                new_path: Some("new-parent".to_string()),
+                file_id: FileId::from("new-parent-id".as_bytes()),
+                new_entry: Some(dir_entry("new-parent-id", "new-parent", Some("root-id"))),
+            },
+        ];
+
+        apply_inventory_delta_streaming(&mut inventory, OneShot(entries.into_iter())).unwrap();
+
+        let moved = inventory.get(&FileId::from("child-id".as_bytes())).unwrap();
+        assert_eq!(
+            moved.parent_id(),
+            Some(&FileId::from("new-parent-id".as_bytes()))
+        );
+    }
+
+    #[test]
+    fn between_is_the_inverse_of_apply() {
+        let root = dir_entry("root-id", "", None);
+        let old = Inventory::from_iter([root.clone(), dir_entry("file-1", "file-1", Some("root-id"))]);
+        let new = Inventory::from_iter([
+            root,
+            dir_entry("file-1", "renamed", Some("root-id")),
+            dir_entry("file-2", "file-2", Some("root-id")),
+        ]);
+
+        let delta = InventoryDelta::between(&old, &new);
+        let applied = delta.apply(&old).unwrap();
+
+        for file_id in new.iter().map(|e| e.file_id()) {
+            assert_eq!(applied.get(file_id), new.get(file_id));
+        }
+        assert_eq!(applied.len(), new.len());
+    }
+
+    #[test]
+    fn between_empty_for_identical_inventories() {
+        let root = dir_entry("root-id", "", None);
+        let inventory = Inventory::from_iter([root, dir_entry("file-1", "file-1", Some("root-id"))]);
+
+        let delta = InventoryDelta::between(&inventory, &inventory);
+
+        assert!(delta.is_empty());
+    }
+
+    #[test]
+    fn binary_round_trip_preserves_embedded_nul_bytes() {
+        let old_name = RevisionId::from("old-rev".as_bytes());
+        let new_name = RevisionId::from("new-rev".as_bytes());
+
+        let mut delta = InventoryDelta::default();
+        add_entry(
+            &mut delta,
+            "file-1",
+            None,
+            Some("file-1"),
+            Some(Entry::File {
+                file_id: FileId::from("file-1".as_bytes()),
+                name: "file-1".to_string(),
+                parent_id: None,
+                revision: None,
+                text_sha1: Some(b"sha\x001-with-a-nul".to_vec()),
+                text_size: Some(5),
+                text_id: None,
+                executable: false,
+            }),
+        );
+        add_entry(
+            &mut delta,
+            "link-1",
+            None,
+            Some("link-1"),
+            Some(Entry::Link {
+                file_id: FileId::from("link-1".as_bytes()),
+                name: "link-1".to_string(),
+                parent_id: None,
+                symlink_target: Some("target\0with-a-nul".to_string()),
+                revision: None,
+            }),
+        );
+
+        let serialized =
+            serialize_inventory_delta_binary(&old_name, &new_name, &delta, true, true).unwrap();
+        let (_, _, _, _, parsed) =
+            parse_inventory_delta_binary(&serialized, Some(true), Some(true)).unwrap();
+
+        let file_entry = parsed.get(0).unwrap().new_entry.as_ref().unwrap();
+        assert_eq!(
+            file_entry,
+            &Entry::File {
+                file_id: FileId::from("file-1".as_bytes()),
+                name: "file-1".to_string(),
+                parent_id: None,
+                revision: None,
+                text_sha1: Some(b"sha\x001-with-a-nul".to_vec()),
+                text_size: Some(5),
+                text_id: None,
+                executable: false,
+            }
+        );
+
+        let link_entry = parsed.get(1).unwrap().new_entry.as_ref().unwrap();
+        assert_eq!(
+            link_entry.symlink_target(),
+            Some("target\0with-a-nul")
+        );
+    }
+}
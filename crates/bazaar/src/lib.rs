@@ -0,0 +1,83 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub mod inventory;
+pub mod inventory_binary;
+pub mod inventory_check;
+pub mod inventory_delta;
+
+/// A stable, content-independent identifier for a versioned file.
+///
+/// File ids are opaque byte strings that stay the same across renames, so
+/// they're used as the key when diffing or looking up entries across
+/// inventories.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(Vec<u8>);
+
+impl FileId {
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Generate a new, unique file id for a file with the given name.
+    ///
+    /// Mirrors the intent of bzrlib's `generate_ids.gen_file_id`: the name
+    /// is mixed in to keep ids human-readable, with a counter appended to
+    /// guarantee uniqueness within a process.
+    pub fn generate(name: &str) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let slug: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .take(20)
+            .collect();
+        FileId(format!("{}-{}", slug, n).into_bytes())
+    }
+}
+
+impl From<Vec<u8>> for FileId {
+    fn from(bytes: Vec<u8>) -> Self {
+        FileId(bytes)
+    }
+}
+
+impl From<&[u8]> for FileId {
+    fn from(bytes: &[u8]) -> Self {
+        FileId(bytes.to_vec())
+    }
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+/// The identifier of a single commit/revision.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RevisionId(Vec<u8>);
+
+impl RevisionId {
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for RevisionId {
+    fn from(bytes: Vec<u8>) -> Self {
+        RevisionId(bytes)
+    }
+}
+
+impl From<&[u8]> for RevisionId {
+    fn from(bytes: &[u8]) -> Self {
+        RevisionId(bytes.to_vec())
+    }
+}
+
+impl fmt::Display for RevisionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
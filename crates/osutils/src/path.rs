@@ -1,10 +1,47 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use unicode_normalization::{is_nfc, UnicodeNormalization};
 
+/// Convert a byte string as found on disk to a `Path`.
+///
+/// On Unix, filenames are arbitrary byte sequences, so this is a zero-cost
+/// reinterpretation of the bytes and never fails or panics.
+#[cfg(unix)]
+pub fn get_path_from_bytes(bytes: &[u8]) -> &Path {
+    use std::os::unix::ffi::OsStrExt;
+    Path::new(std::ffi::OsStr::from_bytes(bytes))
+}
+
+// TODO(jelmer): Windows paths are WTF-8/UTF-16 internally and the bytes a
+// caller hands us here may be MBCS-encoded (e.g. from a legacy API or from
+// a byte-oriented protocol). We don't yet have a WTF-8<->MBCS conversion,
+// so for now just require the bytes to be valid UTF-8.
+#[cfg(windows)]
+pub fn get_path_from_bytes(bytes: &[u8]) -> &Path {
+    Path::new(std::str::from_utf8(bytes).expect("non-UTF8 paths are not yet supported on Windows"))
+}
+
+/// Convert a `Path` to the raw bytes that would be used to represent it on
+/// disk.
+#[cfg(unix)]
+pub fn get_bytes_from_path(path: impl AsRef<Path>) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_ref().as_os_str().as_bytes().to_vec()
+}
+
+// TODO(jelmer): see the TODO on get_path_from_bytes above.
+#[cfg(windows)]
+pub fn get_bytes_from_path(path: impl AsRef<Path>) -> Vec<u8> {
+    path.as_ref()
+        .to_str()
+        .expect("non-UTF8 paths are not yet supported on Windows")
+        .as_bytes()
+        .to_vec()
+}
+
 pub fn is_inside(dir: &Path, fname: &Path) -> bool {
     fname.starts_with(&dir)
 }
@@ -27,6 +64,21 @@ pub fn is_inside_or_parent_of_any(dir_list: &[&Path], fname: &Path) -> bool {
     false
 }
 
+/// Return the minimal subset of `paths` such that no selected path is a
+/// descendant of another selected path.
+///
+/// This is used to deduplicate user-specified paths before a commit/status
+/// walk, so it must be exact: simply comparing each candidate against the
+/// last-kept one (as a naive implementation might) can retain redundant
+/// descendants once more than one top-level root has been selected, because
+/// a later sibling root on the stack can still be an ancestor of the
+/// current candidate even though it isn't the most recently kept one.
+///
+/// Instead, walk the component-sorted candidates while maintaining a stack
+/// of roots that might still be ancestors of what's to come: pop entries
+/// the current path is not inside (they can't be an ancestor of anything
+/// later either, since the sort groups descendants together), then keep
+/// the path only if none of the remaining stack entries contains it.
 pub fn minimum_path_selection(paths: HashSet<&Path>) -> HashSet<&Path> {
     if paths.len() < 2 {
         return paths.clone();
@@ -35,14 +87,103 @@ pub fn minimum_path_selection(paths: HashSet<&Path>) -> HashSet<&Path> {
     let mut sorted_paths: Vec<&Path> = paths.iter().copied().collect();
     sorted_paths.sort_by_key(|&path| path.components().collect::<Vec<_>>());
 
-    let mut search_paths = vec![sorted_paths[0]];
-    for &path in &sorted_paths[1..] {
-        if !is_inside(search_paths.last().unwrap(), path) {
-            search_paths.push(path);
+    let mut selected: Vec<&Path> = Vec::new();
+    let mut stack: Vec<&Path> = Vec::new();
+    for path in sorted_paths {
+        while let Some(&top) = stack.last() {
+            if is_inside(top, path) {
+                break;
+            }
+            stack.pop();
+        }
+        if stack.iter().any(|&root| is_inside(root, path)) {
+            continue;
         }
+        selected.push(path);
+        stack.push(path);
+    }
+
+    selected.into_iter().collect()
+}
+
+#[cfg(test)]
+mod minimum_path_selection_tests {
+    use super::minimum_path_selection;
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    fn selection(paths: &[&str]) -> HashSet<&Path> {
+        let paths: HashSet<&Path> = paths.iter().map(Path::new).collect();
+        minimum_path_selection(paths)
+    }
+
+    fn assert_selection(paths: &[&str], expected: &[&str]) {
+        let expected: HashSet<&Path> = expected.iter().map(Path::new).collect();
+        assert_eq!(selection(paths), expected);
+    }
+
+    #[test]
+    fn test_siblings() {
+        assert_selection(&["a", "b", "c"], &["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        assert_selection(&["a", "a/b", "a/b/c"], &["a"]);
+    }
+
+    #[test]
+    fn test_shared_non_selected_prefix() {
+        assert_selection(&["a/b", "a/c"], &["a/b", "a/c"]);
+    }
+
+    #[test]
+    fn test_sibling_roots_with_interleaved_descendant() {
+        assert_selection(&["a", "b", "a/c"], &["a", "b"]);
+    }
+}
+
+#[cfg(test)]
+mod find_dirs_tests {
+    use super::{ancestor_directories, find_dirs, is_inside_any};
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    #[test]
+    fn yields_every_ancestor_excluding_the_path_itself() {
+        let dirs: Vec<&Path> = find_dirs(Path::new("a/b/c")).collect();
+        assert_eq!(dirs, vec![Path::new("a/b"), Path::new("a"), Path::new("")]);
+    }
+
+    #[test]
+    fn empty_path_yields_nothing() {
+        assert_eq!(find_dirs(Path::new("")).count(), 0);
+    }
+
+    #[test]
+    fn ancestor_directories_dedupes_shared_prefixes_and_prunes_descendants() {
+        let paths: HashSet<&Path> = ["a/b/c", "a/b/d", "a/b"].iter().map(Path::new).collect();
+
+        let dirs = ancestor_directories(paths);
+
+        // "a/b/c" and "a/b/d" are pruned by minimum_path_selection since
+        // "a/b" already covers them; only "a/b" and its ancestors remain.
+        let expected: HashSet<&Path> = ["a/b", "a", ""].iter().map(Path::new).collect();
+        assert_eq!(dirs, expected);
     }
 
-    search_paths.into_iter().collect()
+    #[test]
+    fn ancestor_directories_composes_with_is_inside_any() {
+        let paths: HashSet<&Path> = ["crate/src/lib.rs"].iter().map(Path::new).collect();
+        let dirs = ancestor_directories(paths);
+        let dir_list: Vec<&Path> = dirs.into_iter().collect();
+
+        assert!(is_inside_any(&dir_list, Path::new("crate/src/lib.rs")));
+        // "" (the root) is among the widened ancestors and is trivially a
+        // prefix of every relative path, so check against a directory that
+        // isn't one of the widened ancestors instead of an unrelated file.
+        assert!(!dir_list.contains(&Path::new("other")));
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -102,20 +243,56 @@ pub fn find_executable_on_path(name: &str) -> Option<String> {
     None
 }
 
-pub fn parent_directories(path: &Path) -> impl Iterator<Item = &Path> {
-    let mut path = path;
-    std::iter::from_fn(move || {
-        if let Some(parent) = path.parent() {
-            path = parent;
-            if path.parent().is_none() {
-                None
-            } else {
-                Some(path)
-            }
-        } else {
-            None
-        }
-    })
+/// Iterator over the directories that contain `path`.
+///
+/// Yields every ancestor directory from the immediate parent of `path` up
+/// to and including the root, in that order. Unlike the old
+/// `parent_directories` helper, this neither mutates the starting path
+/// before yielding nor drops the top-level component, and it never panics
+/// on non-UTF8 segments since it operates purely on path components.
+pub struct FindDirs<'a> {
+    inner: std::path::Ancestors<'a>,
+}
+
+impl<'a> Iterator for FindDirs<'a> {
+    type Item = &'a Path;
+
+    fn next(&mut self) -> Option<&'a Path> {
+        self.inner.next()
+    }
+}
+
+impl<'a> std::iter::FusedIterator for FindDirs<'a> {}
+
+pub fn find_dirs(path: &Path) -> FindDirs<'_> {
+    let mut inner = path.ancestors();
+    // The first ancestor is `path` itself, which is not one of its
+    // containing directories.
+    inner.next();
+    FindDirs { inner }
+}
+
+/// Widen `paths` to the minimal set of paths plus every directory that
+/// contains one of them.
+///
+/// This is the directory-set counterpart to [`minimum_path_selection`]:
+/// first prune `paths` down to its minimal covering set with
+/// [`minimum_path_selection`] (so a directory whose descendant is also
+/// selected isn't walked twice), then widen each survivor with
+/// [`find_dirs`] so callers that need "every directory that must be
+/// revisited" (e.g. invalidating cached directory state after a partial
+/// commit/status walk) get a single flat set instead of reimplementing the
+/// ancestor walk and deduplication themselves. The result also lets
+/// [`is_inside_any`] cheaply answer "is this path under one of the
+/// directories that changed".
+pub fn ancestor_directories(paths: HashSet<&Path>) -> HashSet<&Path> {
+    let minimal = minimum_path_selection(paths);
+    let mut dirs: HashSet<&Path> = HashSet::new();
+    for path in minimal {
+        dirs.insert(path);
+        dirs.extend(find_dirs(path));
+    }
+    dirs
 }
 
 pub fn available_backup_name<'a, E>(
@@ -173,6 +350,69 @@ pub fn inaccessible_normalized_filename(path: &Path) -> Option<(PathBuf, bool)>
     })
 }
 
+fn nfc_normalize(s: &str) -> String {
+    if is_nfc(s) {
+        s.to_string()
+    } else {
+        s.nfc().collect::<String>()
+    }
+}
+
+/// Decompose a path into directory, filename, stem and extension, in the
+/// style of the classic `GenericPath` interface.
+///
+/// Unlike the plain `std::path::Path` accessors, every string returned here
+/// is NFC-normalized (reusing the same normalization as
+/// [`accessible_normalized_filename`]), so callers can compare and rewrite
+/// filenames consistently regardless of how the OS delivered their byte
+/// form.
+pub trait GenericPath {
+    fn dirname(&self) -> Option<PathBuf>;
+    fn filename(&self) -> Option<String>;
+    fn filestem(&self) -> Option<String>;
+    fn filetype(&self) -> Option<String>;
+
+    /// Return a new path with just the filestem replaced, preserving the
+    /// extension.
+    fn with_filestem(&self, stem: &str) -> PathBuf;
+
+    /// Return a new path with just the extension replaced.
+    fn with_filetype(&self, filetype: &str) -> PathBuf;
+}
+
+impl GenericPath for Path {
+    fn dirname(&self) -> Option<PathBuf> {
+        self.parent().map(|p| p.to_path_buf())
+    }
+
+    fn filename(&self) -> Option<String> {
+        self.file_name()
+            .map(|name| nfc_normalize(&name.to_string_lossy()))
+    }
+
+    fn filestem(&self) -> Option<String> {
+        self.file_stem()
+            .map(|stem| nfc_normalize(&stem.to_string_lossy()))
+    }
+
+    fn filetype(&self) -> Option<String> {
+        self.extension()
+            .map(|ext| nfc_normalize(&ext.to_string_lossy()))
+    }
+
+    fn with_filestem(&self, stem: &str) -> PathBuf {
+        let stem = nfc_normalize(stem);
+        match self.extension() {
+            Some(ext) => self.with_file_name(format!("{}.{}", stem, ext.to_string_lossy())),
+            None => self.with_file_name(stem),
+        }
+    }
+
+    fn with_filetype(&self, filetype: &str) -> PathBuf {
+        self.with_extension(nfc_normalize(filetype))
+    }
+}
+
 /// Get the unicode normalized path, and if you can access the file.
 ///
 /// On platforms where the system normalizes filenames (Mac OSX),
@@ -267,11 +507,13 @@ pub mod win32 {
     /// running python.exe under cmd.exe return capital C:\\
     /// running win32 python inside a cygwin shell returns lowercase c:\\
     fn fixdrive(path: &Path) -> PathBuf {
-        let mut path_buf = PathBuf::from(path);
-        if let Some(drive) = path_buf.as_os_str().to_str().unwrap().get(..2) {
-            path_buf.push(drive.to_uppercase());
-            path_buf.push(path.to_str().unwrap().get(2..).unwrap());
-            path_buf
+        use crate::path::get_bytes_from_path;
+
+        let bytes = get_bytes_from_path(path);
+        if bytes.len() >= 2 && bytes[1] == b':' {
+            let mut fixed = bytes[..1].to_ascii_uppercase();
+            fixed.extend_from_slice(&bytes[1..]);
+            crate::path::get_path_from_bytes(&fixed).to_path_buf()
         } else {
             path.into()
         }
@@ -279,12 +521,15 @@ pub mod win32 {
 
     /// Return path with directory separators changed to forward slashes
     fn fix_separators(path: &Path) -> PathBuf {
-        if path.to_path_buf().to_str().unwrap().contains('\\') {
-            path.to_path_buf()
-                .to_str()
-                .unwrap()
-                .replace('\\', "/")
-                .into()
+        use crate::path::{get_bytes_from_path, get_path_from_bytes};
+
+        let bytes = get_bytes_from_path(path);
+        if bytes.contains(&b'\\') {
+            let fixed: Vec<u8> = bytes
+                .iter()
+                .map(|&b| if b == b'\\' { b'/' } else { b })
+                .collect();
+            get_path_from_bytes(&fixed).to_path_buf()
         } else {
             path.into()
         }
@@ -296,7 +541,7 @@ pub mod win32 {
 
     pub fn abspath(path: &Path) -> Result<PathBuf, std::io::Error> {
         #[cfg(not(windows))]
-        if ABS_WINDOWS_PATH_RE.is_match(path.to_str().unwrap()) {
+        if ABS_WINDOWS_PATH_RE.is_match(&path.to_string_lossy()) {
             return Ok(path.to_path_buf());
         }
         use path_clean::{clean, PathClean};
@@ -327,11 +572,13 @@ pub mod win32 {
 
         // If the path was empty or only contained root components, return the root component(s)
         if parts.is_empty() {
-            return PathBuf::from(if p.to_str().unwrap().starts_with('\\') {
-                "\\"
-            } else {
-                ""
-            });
+            return PathBuf::from(
+                if crate::path::get_bytes_from_path(&p).starts_with(b"\\") {
+                    "\\"
+                } else {
+                    ""
+                },
+            );
         }
 
         // Join the normalized components into a path string
@@ -438,7 +685,7 @@ pub const MIN_ABS_PATHLENGTH: usize = 3;
 /// NOTE: `base` should not have a trailing slash otherwise you'll get
 /// PathNotChild exceptions regardless of `path`.
 pub fn relpath(base: &Path, path: &Path) -> Option<PathBuf> {
-    if base.to_str().unwrap().len() < MIN_ABS_PATHLENGTH {
+    if get_bytes_from_path(base).len() < MIN_ABS_PATHLENGTH {
         return None;
     }
 
@@ -462,3 +709,283 @@ pub fn relpath(base: &Path, path: &Path) -> Option<PathBuf> {
 
     Some(s.into_iter().rev().collect::<PathBuf>())
 }
+
+lazy_static! {
+    /// Characters that are never legal in a single path component, regardless
+    /// of platform (this is a superset of `VALID_WIN32_PATH_RE`'s forbidden
+    /// set, checked unconditionally so that a tree created on Unix doesn't
+    /// silently contain names that are unusable once checked out on Windows).
+    static ref ILLEGAL_PATH_CHARS_RE: Regex = Regex::new(r#"[:<>*"?|]"#).unwrap();
+}
+
+const WIN32_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Errors raised by [`PathAuditor::audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathAuditError {
+    /// The path contains a `..` component that would escape the root.
+    ParentTraversal(PathBuf),
+    /// The path contains an empty component (e.g. `a//b`).
+    EmptyComponent(PathBuf),
+    /// A component is a Windows-reserved device name (`CON`, `NUL`, ...).
+    ReservedName(PathBuf),
+    /// A component contains characters that are never legal in a filename.
+    IllegalCharacters(PathBuf),
+    /// A prefix of the path resolves through a symlink that points outside
+    /// of the audited root.
+    SymlinkEscape(PathBuf),
+}
+
+impl std::fmt::Display for PathAuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathAuditError::ParentTraversal(p) => {
+                write!(f, "path {} attempts to traverse outside of the root", p.display())
+            }
+            PathAuditError::EmptyComponent(p) => {
+                write!(f, "path {} contains an empty component", p.display())
+            }
+            PathAuditError::ReservedName(p) => {
+                write!(f, "path {} uses a reserved name", p.display())
+            }
+            PathAuditError::IllegalCharacters(p) => {
+                write!(f, "path {} contains illegal characters", p.display())
+            }
+            PathAuditError::SymlinkEscape(p) => {
+                write!(
+                    f,
+                    "path {} traverses a symlink that escapes the root",
+                    p.display()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathAuditError {}
+
+/// Validates that a path is safe to read or write to inside a tree root.
+///
+/// This mirrors Mercurial's `pathauditor`: each candidate path is split into
+/// its cumulative prefixes, and every prefix is checked for `..` traversal,
+/// empty components, illegal/reserved names, and symlinks that escape the
+/// root. Already-audited prefix directories are cached so that auditing many
+/// sibling files doesn't repeatedly `lstat` shared parent directories.
+pub struct PathAuditor {
+    root: PathBuf,
+    /// Maps an already-audited lexical prefix to the real, symlink-resolved
+    /// directory it corresponds to on disk, so repeated calls for sibling
+    /// files don't re-`lstat` shared parent directories, while still
+    /// letting a later `..` in the same call resolve against the real
+    /// location rather than just the lexical one.
+    audited: std::cell::RefCell<HashMap<PathBuf, PathBuf>>,
+}
+
+impl PathAuditor {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        // `audit` compares symlink targets (which `canonicalize()` always
+        // resolves to an absolute, fully-resolved path) against `root` with
+        // a plain `starts_with`, so `root` itself must be canonicalized here
+        // or every symlink would be rejected as escaping a relative or
+        // not-yet-resolved root. Fall back to the given root if it doesn't
+        // exist yet (e.g. a tree being created from scratch).
+        let root = root.canonicalize().unwrap_or(root);
+        PathAuditor {
+            root,
+            audited: std::cell::RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Check that `path` (relative to the root) is safe to operate on.
+    pub fn audit(&self, path: &Path) -> Result<(), PathAuditError> {
+        if path.is_absolute() {
+            return Err(PathAuditError::ParentTraversal(path.to_path_buf()));
+        }
+
+        let mut prefix = PathBuf::new();
+        // The real, symlink-resolved directory `prefix` currently
+        // corresponds to on disk, starting at `self.root`. Tracked
+        // separately from `prefix` because a symlinked component can make
+        // the two diverge: a `..` must walk back up from wherever the
+        // symlink actually points, not from the lexical parent, or a
+        // symlink that lands shallower than its lexical position (e.g.
+        // `root/a` -> `root` itself) would let a trailing `..` step
+        // outside `root` while the lexical depth counter alone still looks
+        // safe.
+        let mut real = self.root.clone();
+        let mut depth: isize = 0;
+        for component in path.components() {
+            let name = match component {
+                std::path::Component::Normal(name) => name,
+                std::path::Component::CurDir => continue,
+                std::path::Component::ParentDir => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return Err(PathAuditError::ParentTraversal(path.to_path_buf()));
+                    }
+                    prefix.pop();
+                    real = real.parent().map_or_else(|| real.clone(), Path::to_path_buf);
+                    if real != self.root && !is_inside(&self.root, &real) {
+                        return Err(PathAuditError::SymlinkEscape(path.to_path_buf()));
+                    }
+                    continue;
+                }
+                _ => return Err(PathAuditError::ParentTraversal(path.to_path_buf())),
+            };
+
+            if name.is_empty() {
+                return Err(PathAuditError::EmptyComponent(path.to_path_buf()));
+            }
+
+            let name_str = name.to_string_lossy();
+            if ILLEGAL_PATH_CHARS_RE.is_match(&name_str) {
+                return Err(PathAuditError::IllegalCharacters(path.to_path_buf()));
+            }
+            let stem = name_str.split('.').next().unwrap_or(&name_str);
+            if WIN32_RESERVED_NAMES
+                .iter()
+                .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+            {
+                return Err(PathAuditError::ReservedName(path.to_path_buf()));
+            }
+
+            depth += 1;
+            prefix.push(name);
+
+            if let Some(cached_real) = self.audited.borrow().get(&prefix) {
+                real = cached_real.clone();
+                continue;
+            }
+
+            let full = real.join(name);
+            if let Ok(metadata) = full.symlink_metadata() {
+                if metadata.file_type().is_symlink() {
+                    if let Ok(target) = full.canonicalize() {
+                        if !is_inside(&self.root, &target) {
+                            return Err(PathAuditError::SymlinkEscape(path.to_path_buf()));
+                        }
+                        real = target;
+                        self.audited.borrow_mut().insert(prefix.clone(), real.clone());
+                        continue;
+                    }
+                }
+            }
+
+            real = full;
+            self.audited.borrow_mut().insert(prefix.clone(), real.clone());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod path_auditor_tests {
+    use super::{PathAuditError, PathAuditor};
+    use std::path::{Path, PathBuf};
+
+    /// A scratch directory under the system temp dir, removed on drop.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "breezy-path-auditor-test-{}-{}-{}",
+                name,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            ScratchDir(dir)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // `set_current_dir` is process-global, so serialize the one test that
+    // needs a cwd-relative root against any other test in this module that
+    // might also touch it.
+    static CWD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn relative_root_accepts_safe_symlink() {
+        let _guard = CWD_LOCK.lock().unwrap();
+
+        let scratch = ScratchDir::new("relative-root-safe");
+        std::fs::create_dir_all(scratch.path().join("real")).unwrap();
+        std::fs::write(scratch.path().join("real/target.txt"), b"hi").unwrap();
+        std::os::unix::fs::symlink(
+            scratch.path().join("real"),
+            scratch.path().join("link"),
+        )
+        .unwrap();
+
+        // Exercise the bug directly: a root that is relative to the process
+        // cwd (not pre-canonicalized by the caller) must still accept
+        // symlinks that resolve inside the tree.
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(scratch.path().parent().unwrap()).unwrap();
+        let relative_root = PathBuf::from(scratch.path().file_name().unwrap());
+        let auditor = PathAuditor::new(relative_root);
+        let result = auditor.audit(Path::new("link/target.txt"));
+        std::env::set_current_dir(cwd).unwrap();
+
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn rejects_symlink_escaping_root() {
+        let scratch = ScratchDir::new("escape");
+        let outside = ScratchDir::new("escape-outside");
+        std::fs::write(outside.path().join("secret.txt"), b"secret").unwrap();
+        std::os::unix::fs::symlink(outside.path(), scratch.path().join("link")).unwrap();
+
+        let auditor = PathAuditor::new(scratch.path());
+        let result = auditor.audit(Path::new("link/secret.txt"));
+
+        assert!(matches!(result, Err(PathAuditError::SymlinkEscape(_))));
+    }
+
+    #[test]
+    fn rejects_parent_dir_after_a_symlink_that_lands_above_its_lexical_depth() {
+        let scratch = ScratchDir::new("escape-via-parent-dir");
+        // "a" is a symlink back to the root itself, so it sits one real
+        // directory shallower than its lexical depth: stepping into "a"
+        // and then back out with ".." really steps one level above root.
+        std::os::unix::fs::symlink(scratch.path(), scratch.path().join("a")).unwrap();
+
+        let auditor = PathAuditor::new(scratch.path());
+        let result = auditor.audit(Path::new("a/.."));
+
+        assert!(matches!(result, Err(PathAuditError::SymlinkEscape(_))));
+    }
+
+    #[test]
+    fn accepts_safe_symlink_with_absolute_root() {
+        let scratch = ScratchDir::new("absolute-root-safe");
+        std::fs::create_dir_all(scratch.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(
+            scratch.path().join("real"),
+            scratch.path().join("link"),
+        )
+        .unwrap();
+
+        let auditor = PathAuditor::new(scratch.path());
+        assert_eq!(auditor.audit(Path::new("link/file.txt")), Ok(()));
+    }
+}
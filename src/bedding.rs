@@ -1,10 +1,50 @@
+use lazy_static::lazy_static;
 use log::debug;
 use std::env;
 use std::fs::create_dir;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
 // TODO(jelmer): Rely on the directories crate instead
 
+lazy_static! {
+    static ref LAYOUT_ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// An override root that redirects every bedding path function to
+/// subdirectories of a single location, instead of each one independently
+/// consulting `BRZ_HOME`/`XDG_*`.
+///
+/// This is meant for testing, sandboxed CI, and portable "run from a USB
+/// stick" installs: a test harness can point breezy at a `tempdir` with one
+/// call and get full isolation, and a portable install can keep everything
+/// self-contained under one root.
+///
+/// When a root is set, [`config_dir`], [`cache_dir`], [`crash_dir`] and
+/// [`runtime_dir`] (and therefore [`config_path`], [`locations_config_path`],
+/// [`authentication_config_path`] and [`user_ignore_config_path`], which are
+/// all derived from `config_dir`) resolve to `root/config`, `root/cache`,
+/// `root/crash` and `root/runtime` respectively.
+pub struct Layout;
+
+impl Layout {
+    /// Redirect every bedding path function to subdirectories of `root`.
+    pub fn set_root(root: impl Into<PathBuf>) {
+        *LAYOUT_ROOT.write().unwrap() = Some(root.into());
+    }
+
+    /// Remove any previously set override root, reverting to the normal
+    /// `BRZ_HOME`/`XDG_*`-based resolution.
+    pub fn clear_root() {
+        *LAYOUT_ROOT.write().unwrap() = None;
+    }
+
+    /// The currently configured override root, if any.
+    pub fn root() -> Option<PathBuf> {
+        LAYOUT_ROOT.read().unwrap().clone()
+    }
+}
+
 /// Make sure a configuration directory exists.
 ///
 /// This makes sure that the directory exists.
@@ -113,6 +153,10 @@ impl ToString for ConfigDirKind {
 /// Mac OS X and Linux. If the breezy config directory doesn't exist but
 /// the bazaar one (see bazaar_config_dir()) does, use that instead.
 pub fn _config_dir() -> std::io::Result<(PathBuf, ConfigDirKind)> {
+    if let Some(root) = Layout::root() {
+        return Ok((root.join("config"), ConfigDirKind::Breezy));
+    }
+
     // TODO: Global option --config-dir to override this.
     let base = env::var("BRZ_HOME").map(PathBuf::from).ok();
     #[cfg(windows)]
@@ -144,6 +188,19 @@ pub fn _config_dir() -> std::io::Result<(PathBuf, ConfigDirKind)> {
             );
             Ok((bazaar_dir, ConfigDirKind::Bazaar))
         } else {
+            // Neither the Breezy nor the legacy Bazaar directory exists yet.
+            // On macOS, prefer the native Application Support location,
+            // unless the user has explicitly asked for an XDG directory via
+            // $XDG_CONFIG_HOME.
+            #[cfg(target_os = "macos")]
+            if env::var("XDG_CONFIG_HOME").is_err() {
+                let app_support_dir = breezy_osutils::get_home_dir()
+                    .expect("no home directory")
+                    .join("Library")
+                    .join("Application Support")
+                    .join("breezy");
+                return Ok((app_support_dir, ConfigDirKind::Breezy));
+            }
             Ok((breezy_dir, ConfigDirKind::Breezy))
         }
     }
@@ -189,6 +246,10 @@ pub fn crash_dir() -> PathBuf {
     // which may be monitored by apport. It can be overridden by
     // $APPORT_CRASH_DIR.
 
+    if let Some(root) = Layout::root() {
+        return root.join("crash");
+    }
+
     #[cfg(windows)]
     {
         config_dir().join("Crash")
@@ -205,7 +266,41 @@ pub fn crash_dir() -> PathBuf {
     }
 }
 
+/// Return the directories that should be searched for system/user
+/// configuration files, in precedence order (most-specific first).
+///
+/// Per the freedesktop base-dir spec, breezy should honor the
+/// colon-separated `$XDG_CONFIG_DIRS` (defaulting to `/etc/xdg`) for
+/// system-wide `breezy.conf`/`locations.conf`/`ignore` files, in addition to
+/// the per-user directory returned by [`config_dir`]. A future config-loading
+/// layer should read all of these, with earlier entries overriding keys
+/// from later ones rather than picking a single directory wholesale; this
+/// crate does not yet have such a layer, so this function only resolves
+/// the path list and nothing currently calls it.
+///
+/// On Windows this is a no-op and just returns the user config directory,
+/// since there is no equivalent of `XDG_CONFIG_DIRS` there.
+pub fn config_search_paths() -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = vec![config_dir()?];
+
+    #[cfg(not(windows))]
+    {
+        let xdg_config_dirs = env::var("XDG_CONFIG_DIRS").unwrap_or_else(|_| "/etc/xdg".to_string());
+        for dir in xdg_config_dirs.split(':').filter(|d| !d.is_empty()) {
+            paths.push(PathBuf::from(dir).join("breezy"));
+        }
+    }
+
+    Ok(paths)
+}
+
 pub fn cache_dir() -> std::io::Result<PathBuf> {
+    if let Some(root) = Layout::root() {
+        let cache_dir = root.join("cache");
+        std::fs::create_dir_all(&cache_dir)?;
+        return Ok(cache_dir);
+    }
+
     // Return the cache directory to use.
     let mut base: Option<PathBuf> = env::var("BRZ_HOME").ok().map(PathBuf::from);
 
@@ -227,11 +322,23 @@ pub fn cache_dir() -> std::io::Result<PathBuf> {
             base = None;
         }
         if base.is_none() {
-            base = Some(
-                breezy_osutils::get_home_dir()
-                    .expect("no home directory")
-                    .join(".cache"),
-            );
+            #[cfg(target_os = "macos")]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join("Library")
+                        .join("Caches"),
+                );
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join(".cache"),
+                );
+            }
         }
     }
 
@@ -243,3 +350,402 @@ pub fn cache_dir() -> std::io::Result<PathBuf> {
 
     Ok(cache_dir)
 }
+
+/// Return the per-user data directory to use.
+///
+/// Per the XDG base-dir spec, mutable data (such as plugins and aliases)
+/// should live under `$XDG_DATA_HOME` (`~/.local/share`) rather than under
+/// the config tree, since bundling it with config causes problems with
+/// backup/sync and with read-only config mounts.
+pub fn data_dir() -> std::io::Result<PathBuf> {
+    if let Some(root) = Layout::root() {
+        let data_dir = root.join("data");
+        std::fs::create_dir_all(&data_dir)?;
+        return Ok(data_dir);
+    }
+
+    let mut base: Option<PathBuf> = env::var("BRZ_HOME").ok().map(PathBuf::from);
+
+    #[cfg(windows)]
+    {
+        if base.is_none() {
+            base = win32utils::get_local_appdata_location();
+        }
+        if base.is_none() {
+            base = win32utils::get_home_location();
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
+            base = Some(PathBuf::from(xdg_data_home));
+        } else {
+            base = None;
+        }
+        if base.is_none() {
+            #[cfg(target_os = "macos")]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join("Library")
+                        .join("Application Support"),
+                );
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join(".local")
+                        .join("share"),
+                );
+            }
+        }
+    }
+
+    let data_dir = base.unwrap().join("breezy");
+    std::fs::create_dir_all(&data_dir)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", e, data_dir.display())))?;
+
+    Ok(data_dir)
+}
+
+/// Return the per-user state directory to use.
+///
+/// Per the XDG base-dir spec, mutable state (such as logs and history)
+/// should live under `$XDG_STATE_HOME` (`~/.local/state`) rather than under
+/// the config tree.
+pub fn state_dir() -> std::io::Result<PathBuf> {
+    if let Some(root) = Layout::root() {
+        let state_dir = root.join("state");
+        std::fs::create_dir_all(&state_dir)?;
+        return Ok(state_dir);
+    }
+
+    let mut base: Option<PathBuf> = env::var("BRZ_HOME").ok().map(PathBuf::from);
+
+    #[cfg(windows)]
+    {
+        if base.is_none() {
+            base = win32utils::get_local_appdata_location();
+        }
+        if base.is_none() {
+            base = win32utils::get_home_location();
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        if let Ok(xdg_state_home) = env::var("XDG_STATE_HOME") {
+            base = Some(PathBuf::from(xdg_state_home));
+        } else {
+            base = None;
+        }
+        if base.is_none() {
+            #[cfg(target_os = "macos")]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join("Library")
+                        .join("Application Support"),
+                );
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                base = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join(".local")
+                        .join("state"),
+                );
+            }
+        }
+    }
+
+    let state_dir = base.unwrap().join("breezy");
+    std::fs::create_dir_all(&state_dir)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", e, state_dir.display())))?;
+
+    Ok(state_dir)
+}
+
+/// Resolve the path of a file that used to live directly in the config
+/// directory but has moved to `data_dir()`/`state_dir()`.
+///
+/// If `filename` already exists under the (legacy) config directory, that
+/// path is returned so existing installs keep working; otherwise the path
+/// under `new_dir` is returned.
+fn path_with_legacy_fallback(new_dir: &Path, filename: &str) -> std::io::Result<PathBuf> {
+    let legacy = config_dir()?.join(filename);
+    if legacy.exists() {
+        Ok(legacy)
+    } else {
+        Ok(new_dir.join(filename))
+    }
+}
+
+/// Return the path of a per-user data file, honoring a pre-existing file of
+/// the same name under the legacy config directory.
+pub fn data_path(filename: &str) -> std::io::Result<PathBuf> {
+    path_with_legacy_fallback(&data_dir()?, filename)
+}
+
+/// Return the path of a per-user state file, honoring a pre-existing file of
+/// the same name under the legacy config directory.
+pub fn state_path(filename: &str) -> std::io::Result<PathBuf> {
+    path_with_legacy_fallback(&state_dir()?, filename)
+}
+
+/// Return the directory to use for ephemeral per-session runtime state,
+/// such as smart-server sockets, lock files, and other launchd/systemd
+/// managed transient data.
+///
+/// This prefers `$XDG_RUNTIME_DIR/breezy`, since that's the spec-correct,
+/// tmpfs-backed, `0700` location maintained by the session manager. When
+/// `XDG_RUNTIME_DIR` isn't set it falls back to `~/.local/share/breezy-runtime`
+/// on Unix (deliberately distinct from [`data_dir`]'s
+/// `~/.local/share/breezy`, so enforcing `0700` here never clobbers
+/// permissions on the persistent, shared plugins/aliases directory),
+/// `~/Library/breezy` on macOS, and the local-appdata location on Windows.
+///
+/// The directory is created if it doesn't exist yet (like [`cache_dir`]),
+/// and on Unix its permissions are verified/enforced to be `0700` since it
+/// may hold sockets and secrets.
+pub fn runtime_dir() -> std::io::Result<PathBuf> {
+    if let Some(root) = Layout::root() {
+        let runtime_dir = root.join("runtime");
+        std::fs::create_dir_all(&runtime_dir)?;
+        return Ok(runtime_dir);
+    }
+
+    let mut base: Option<PathBuf> = env::var("BRZ_HOME").ok().map(PathBuf::from);
+    // Set directly (bypassing `base`/the generic `.join("breezy")` below)
+    // only for the one fallback that would otherwise collide with
+    // `data_dir()`'s own fallback; see the comment at its use below.
+    let mut runtime_dir: Option<PathBuf> = None;
+
+    #[cfg(windows)]
+    {
+        if base.is_none() {
+            base = win32utils::get_local_appdata_location();
+        }
+        if base.is_none() {
+            base = win32utils::get_home_location();
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        if base.is_none() {
+            if let Ok(xdg_runtime_dir) = env::var("XDG_RUNTIME_DIR") {
+                base = Some(PathBuf::from(xdg_runtime_dir));
+            }
+        }
+        if base.is_none() {
+            #[cfg(target_os = "macos")]
+            {
+                base = Some(breezy_osutils::get_home_dir().expect("no home directory").join("Library"));
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                // Distinct from data_dir()'s `~/.local/share/breezy`: on a
+                // box without a full systemd user session (e.g. SSH,
+                // containers) `$XDG_RUNTIME_DIR` is commonly unset, and
+                // sharing data_dir()'s path here would mean the first
+                // caller to need a runtime dir force-chmods the
+                // persistent, shared plugins/aliases directory to `0700`.
+                runtime_dir = Some(
+                    breezy_osutils::get_home_dir()
+                        .expect("no home directory")
+                        .join(".local")
+                        .join("share")
+                        .join("breezy-runtime"),
+                );
+            }
+        }
+    }
+
+    let runtime_dir = runtime_dir.unwrap_or_else(|| base.unwrap().join("breezy"));
+
+    std::fs::create_dir_all(&runtime_dir).map_err(|e| {
+        std::io::Error::new(e.kind(), format!("{}: {}", e, runtime_dir.display()))
+    })?;
+
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(&runtime_dir)?;
+        let mut permissions = metadata.permissions();
+        if permissions.mode() & 0o777 != 0o700 {
+            permissions.set_mode(0o700);
+            std::fs::set_permissions(&runtime_dir, permissions)?;
+        }
+    }
+
+    Ok(runtime_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// These tests mutate process-global environment variables, so
+    /// serialize them against each other to avoid one test observing
+    /// another's temporary overrides.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Set `vars` for the duration of `f`, then restore whatever was set
+    /// (or unset) beforehand.
+    fn with_env<F: FnOnce()>(vars: &[(&str, Option<&str>)], f: F) {
+        let saved: Vec<(&str, Option<String>)> =
+            vars.iter().map(|(key, _)| (*key, env::var(key).ok())).collect();
+
+        for (key, value) in vars {
+            match value {
+                Some(v) => env::set_var(key, v),
+                None => env::remove_var(key),
+            }
+        }
+
+        f();
+
+        for (key, value) in saved {
+            match value {
+                Some(v) => env::set_var(key, v),
+                None => env::remove_var(key),
+            }
+        }
+    }
+
+    fn scratch_home(name: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(format!(
+            "breezy-bedding-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        home
+    }
+
+    /// Ensures [`Layout::clear_root`] runs even if an assertion inside the
+    /// test panics, so a failing test doesn't leak an override root into
+    /// whichever test happens to run next.
+    struct LayoutGuard;
+
+    impl LayoutGuard {
+        fn set(root: impl Into<PathBuf>) -> Self {
+            Layout::set_root(root);
+            LayoutGuard
+        }
+    }
+
+    impl Drop for LayoutGuard {
+        fn drop(&mut self) {
+            Layout::clear_root();
+        }
+    }
+
+    #[test]
+    fn layout_root_redirects_every_bedding_path_under_it() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let root = scratch_home("layout-root");
+        let _layout = LayoutGuard::set(root.clone());
+
+        assert_eq!(config_dir().unwrap(), root.join("config"));
+        assert_eq!(config_path().unwrap(), root.join("config").join("breezy.conf"));
+        assert_eq!(
+            locations_config_path().unwrap(),
+            root.join("config").join("locations.conf")
+        );
+        assert_eq!(cache_dir().unwrap(), root.join("cache"));
+        assert_eq!(data_dir().unwrap(), root.join("data"));
+        assert_eq!(state_dir().unwrap(), root.join("state"));
+        assert_eq!(runtime_dir().unwrap(), root.join("runtime"));
+        assert_eq!(crash_dir(), root.join("crash"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn layout_clear_root_restores_normal_resolution() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let home = scratch_home("layout-clear-root");
+        let root = scratch_home("layout-clear-root-override");
+
+        with_env(
+            &[
+                ("BRZ_HOME", None),
+                ("XDG_DATA_HOME", None),
+                ("HOME", Some(home.to_str().unwrap())),
+            ],
+            || {
+                let layout = LayoutGuard::set(&root);
+                assert_eq!(data_dir().unwrap(), root.join("data"));
+
+                Layout::clear_root();
+                assert_eq!(data_dir().unwrap(), home.join(".local/share/breezy"));
+                drop(layout);
+            },
+        );
+
+        let _ = std::fs::remove_dir_all(&home);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn data_dir_falls_back_to_xdg_data_home_default() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let home = scratch_home("data-dir-fallback");
+
+        with_env(
+            &[
+                ("BRZ_HOME", None),
+                ("XDG_DATA_HOME", None),
+                ("HOME", Some(home.to_str().unwrap())),
+            ],
+            || {
+                let dir = data_dir().unwrap();
+                assert_eq!(dir, home.join(".local/share/breezy"));
+            },
+        );
+
+        let _ = std::fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn runtime_dir_falls_back_to_a_directory_distinct_from_data_dir() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let home = scratch_home("runtime-dir-fallback");
+
+        with_env(
+            &[
+                ("BRZ_HOME", None),
+                ("XDG_RUNTIME_DIR", None),
+                ("XDG_DATA_HOME", None),
+                ("HOME", Some(home.to_str().unwrap())),
+            ],
+            || {
+                let data = data_dir().unwrap();
+                let runtime = runtime_dir().unwrap();
+
+                // Without a systemd-managed $XDG_RUNTIME_DIR (e.g. over
+                // SSH or in a container), runtime_dir() must not resolve
+                // to the same path as data_dir() — otherwise enforcing
+                // 0700 on it would clobber permissions on the shared,
+                // persistent plugins/aliases directory.
+                assert_eq!(runtime, home.join(".local/share/breezy-runtime"));
+                assert_ne!(runtime, data);
+            },
+        );
+
+        let _ = std::fs::remove_dir_all(&home);
+    }
+}